@@ -1,5 +1,6 @@
 use gl;
 use gl::types::*;
+use gl33::state;
 use gl33::token::GL33;
 use luminance::buffer;
 use std::cmp::Ordering::*;
@@ -38,7 +39,8 @@ unsafe impl buffer::HasBuffer for GL33 {
   }
 
   fn free(buffer: &mut Self::ABuffer) {
-    unsafe { gl::DeleteBuffers(1, &buffer.handle) }
+    unsafe { gl::DeleteBuffers(1, &buffer.handle) };
+    state::forget_buffer(buffer.handle);
   }
 
   fn write_whole<T>(buffer: &Self::ABuffer, values: &[T]) -> Result<(), buffer::BufferError> {
@@ -0,0 +1,55 @@
+use gl;
+use gl::types::*;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::ffi::CStr;
+
+use gl33::state::{self, ContextId};
+
+// Extensions supported by the contexts used on this thread, queried once per context.
+thread_local!(static EXTENSIONS: RefCell<HashMap<ContextId, HashSet<String>>> = RefCell::new(HashMap::new()));
+
+// Check whether the current context supports an extension (e.g. "GL_ARB_invalidate_subdata").
+pub fn has_extension(name: &str) -> bool {
+  EXTENSIONS.with(|extensions| {
+    extensions.borrow_mut().entry(state::current_context()).or_insert_with(get_extensions).contains(name)
+  })
+}
+
+// Check whether the current context is at least of a given OpenGL version.
+pub fn has_version(major: GLint, minor: GLint) -> bool {
+  let mut context_major: GLint = 0;
+  let mut context_minor: GLint = 0;
+
+  unsafe {
+    gl::GetIntegerv(gl::MAJOR_VERSION, &mut context_major);
+    gl::GetIntegerv(gl::MINOR_VERSION, &mut context_minor);
+  }
+
+  (context_major, context_minor) >= (major, minor)
+}
+
+// Forget the extensions of the current context, so that they’re queried again.
+pub fn forget_extensions() {
+  EXTENSIONS.with(|extensions| {
+    extensions.borrow_mut().remove(&state::current_context());
+  })
+}
+
+fn get_extensions() -> HashSet<String> {
+  let mut extension_nb: GLint = 0;
+
+  unsafe {
+    gl::GetIntegerv(gl::NUM_EXTENSIONS, &mut extension_nb);
+
+    (0..extension_nb as GLuint).filter_map(|i| {
+      let name = gl::GetStringi(gl::EXTENSIONS, i);
+
+      if name.is_null() {
+        None
+      } else {
+        Some(CStr::from_ptr(name as *const _).to_string_lossy().into_owned())
+      }
+    }).collect()
+  }
+}
@@ -1,10 +1,20 @@
 use gl;
 use gl::types::*;
-use gl33::texture::{GLTexture, create_texture, to_target};
+use gl33::extension::{has_extension, has_version};
+use gl33::state;
+use gl33::texture::{GLTexture, create_multisample_texture, create_texture, from_filter, set_pack_alignment,
+                    to_target};
 use gl33::token::GL33;
 use luminance::framebuffer::{self, ColorSlot, DepthSlot, FramebufferError, HasFramebuffer, Result};
-use luminance::texture::{Dimensionable, Layerable};
+use luminance::pixel::{Pixel, PixelFormat, is_color_pixel, is_depth_pixel};
+use luminance::texture::{self, CubeFace, Dim, Dimensionable, Filter, Layerable, Layering, TextureError};
+use pixel::{gl_pixel_format, gl_transfer_format, pixel_components, zeroed_texels};
+use std::cell::RefCell;
 use std::default::Default;
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::os::raw::c_void;
+use std::ptr;
 
 pub type Framebuffer<L, D, CS, DS> = framebuffer::Framebuffer<GL33, L, D, CS, DS>;
 
@@ -13,108 +23,822 @@ pub struct GLFramebuffer {
   pub renderbuffer: Option<GLuint>,
   pub w: u32,
   pub h: u32,
+  pub samples: Option<u32>, // number of samples per pixel, if multisampled
+  pub color_formats: Vec<GLenum>, // internal formats of the color attachments
+  pub depth_format: Option<GLenum>, // internal format of the depth attachment
+  pub stencil_format: Option<GLenum>, // internal format of the stencil attachment
+  pub stencil_texture: Option<GLTexture>, // depth-stencil or stencil texture, if any
+  pub stencil_renderbuffer: Option<GLuint>, // depth-stencil or stencil renderbuffer, if any
+  pub texture_target: GLenum, // target of the color and depth textures
+  pub color_textures: Vec<GLuint>, // color textures, in attachment order
+  pub depth_texture: Option<GLuint>, // depth texture, if the depth slot is a texture
+  pub attachment: AttachmentTarget, // part of the textures currently rendered into
+  pub borrows_textures: bool // whether some attachments are existing textures, borrowed from elsewhere
 }
 
-impl HasFramebuffer for GL33 {
-  type Framebuffer = GLFramebuffer;
+impl GLFramebuffer {
+  // Does the framebuffer have a depth attachment? The default framebuffer is assumed to have one.
+  pub fn has_depth(&self) -> bool {
+    self.handle == 0 || self.depth_format.is_some()
+  }
 
-  fn new_framebuffer<L, D, CS, DS>(size: D::Size, mipmaps: usize) -> Result<(Self::Framebuffer, Vec<Self::ATexture>, Option<Self::ATexture>)>
-    where L: Layerable,
-          D: Dimensionable,
-          D::Size: Copy,
-          CS: ColorSlot<Self, L, D>,
-          DS: DepthSlot<Self, L, D> {
-    let mut framebuffer: GLuint = 0;
-    let color_formats = CS::color_formats();
-    let depth_format = DS::depth_format();
-    let target = to_target(L::layering(), D::dim());
-    let mut textures: Vec<GLuint> = vec![0; color_formats.len() + if depth_format.is_some() { 1 } else { 0 }]; // FIXME: remove that (inference)
-    let mut depth_texture: Option<GLuint> = None;
-    let mut depth_renderbuffer: Option<GLuint> = None;
+  // Does the framebuffer have a stencil attachment? The default framebuffer is assumed to have one.
+  pub fn has_stencil(&self) -> bool {
+    self.handle == 0 || self.stencil_format.is_some()
+  }
+
+  // Size of the part of the textures currently rendered into.
+  pub fn viewport_size(&self) -> (u32, u32) {
+    let level = self.attachment.level() as u32;
+    ((self.w >> level).max(1), (self.h >> level).max(1))
+  }
+
+  // Change the part of the color and depth textures rendered into (mipmap level, cubemap face or
+  // layer). No storage is reallocated, so this is cheap enough to be done between passes.
+  //
+  // Depth-stencil and stencil attachments are left untouched. If the framebuffer ends up incomplete,
+  // the previous part is attached back.
+  pub fn retarget(&mut self, attachment: AttachmentTarget) -> ::std::result::Result<(), GLFramebufferError> {
+    check_attachment_target(self.texture_target, attachment)?;
+
+    for texture in self.color_textures.iter().chain(&self.depth_texture) {
+      check_attachment_bounds(self.texture_target, *texture, attachment)?;
+    }
+
+    state::bind_framebuffer(gl::FRAMEBUFFER, self.handle);
+
+    self.attach_textures(attachment);
+
+    let status = get_status(self.texture_target);
+
+    if status.is_some() {
+      let previous = self.attachment;
+      self.attach_textures(previous);
+    }
+
+    state::bind_framebuffer(gl::FRAMEBUFFER, 0);
+
+    match status {
+      Some(incomplete) => Err(incomplete),
+      None => {
+        self.attachment = attachment;
+        Ok(())
+      }
+    }
+  }
+
+  // Attach a part of the color and depth textures to the framebuffer, which must be bound.
+  fn attach_textures(&self, attachment: AttachmentTarget) {
+    for (i, texture) in self.color_textures.iter().enumerate() {
+      attach_texture(gl::COLOR_ATTACHMENT0 + i as GLenum, *texture, attachment);
+    }
+
+    if let Some(texture) = self.depth_texture {
+      attach_texture(gl::DEPTH_ATTACHMENT, texture, attachment);
+    }
+  }
+
+  // Resize the framebuffer, reallocating all of its attachments at the new size.
+  //
+  // The OpenGL objects are kept, so textures bound elsewhere stay valid; however, the sizes stored in
+  // the luminance textures of the slots are not updated. Resizing the default framebuffer only
+  // changes its viewport size.
+  //
+  // Framebuffers rendering into existing textures cannot be resized, as their textures may be used
+  // elsewhere. If the framebuffer ends up incomplete, the attachments are reallocated at their
+  // previous size.
+  pub fn resize(&mut self, w: u32, h: u32) -> ::std::result::Result<(), GLFramebufferError> {
+    if self.borrows_textures {
+      return Err(GLFramebufferError::BorrowedAttachments);
+    }
+
+    if self.handle == 0 {
+      self.w = w;
+      self.h = h;
+      return Ok(());
+    }
+
+    // every mipmap level is reallocated, so the part rendered into must exist at any size
+    for texture in self.color_textures.iter().chain(&self.depth_texture) {
+      check_attachment_bounds(self.texture_target, *texture, self.attachment)?;
+    }
+
+    self.reallocate_attachments(w, h);
+
+    state::bind_framebuffer(gl::FRAMEBUFFER, self.handle);
+
+    let status = get_status(self.texture_target);
+
+    if status.is_some() {
+      let (previous_w, previous_h) = (self.w, self.h);
+      self.reallocate_attachments(previous_w, previous_h);
+    }
+
+    state::bind_framebuffer(gl::FRAMEBUFFER, 0);
+
+    match status {
+      Some(incomplete) => Err(incomplete),
+      None => {
+        self.w = w;
+        self.h = h;
+        Ok(())
+      }
+    }
+  }
+
+  // Reallocate the storage of all the attachments at a given size.
+  fn reallocate_attachments(&self, w: u32, h: u32) {
+    for (texture, iformat) in self.color_textures.iter().zip(&self.color_formats) {
+      reallocate_texture(self.texture_target, *texture, *iformat, w, h, self.samples);
+    }
+
+    if let (Some(texture), Some(iformat)) = (self.depth_texture, self.depth_format) {
+      reallocate_texture(self.texture_target, texture, iformat, w, h, self.samples);
+    }
+
+    if let (Some(renderbuffer), Some(iformat)) = (self.renderbuffer, self.depth_format) {
+      reallocate_renderbuffer(renderbuffer, iformat, w, h, self.samples);
+    }
+
+    if let (Some(ref texture), Some(iformat)) = (self.stencil_texture.as_ref(), self.stencil_format) {
+      reallocate_texture(texture.target, texture.handle, iformat, w, h, self.samples);
+    }
+
+    if let (Some(renderbuffer), Some(iformat)) = (self.stencil_renderbuffer, self.stencil_format) {
+      reallocate_renderbuffer(renderbuffer, iformat, w, h, self.samples);
+    }
+  }
+
+  // Rectangle covering the whole framebuffer.
+  pub fn rect(&self) -> Rect {
+    Rect::new(0, 0, self.w, self.h)
+  }
+
+  // Copy a region of this framebuffer into a region of another framebuffer.
+  //
+  // Every color attachment is copied into the color attachment of the same index of the target
+  // framebuffer; the default framebuffer only receives the first one. If the regions have different
+  // sizes, the copy is scaled with the given filter.
+  pub fn blit(&self, target: &GLFramebuffer, src: Rect, dst: Rect, mask: BlitMask, filter: Filter) -> ::std::result::Result<(), BlitError> {
+    check_blit(self, target, src, dst, mask, filter)?;
+
+    let (sx0, sy0, sx1, sy1) = src.corners().ok_or(BlitError::RectOverflow(src))?;
+    let (dx0, dy0, dx1, dy1) = dst.corners().ok_or(BlitError::RectOverflow(dst))?;
+    let depth_stencil_bits = if mask.depth { gl::DEPTH_BUFFER_BIT } else { 0 } | if mask.stencil { gl::STENCIL_BUFFER_BIT } else { 0 };
+    let filter = from_filter(filter);
 
     unsafe {
-      gl::GenFramebuffers(1, &mut framebuffer);
+      state::bind_framebuffer(gl::READ_FRAMEBUFFER, self.handle);
+      state::bind_framebuffer(gl::DRAW_FRAMEBUFFER, target.handle);
+
+      let color_nb = if mask.color { blit_color_nb(self, target) } else { 0 };
 
-      gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
+      for i in 0..color_nb {
+        let read_buffer = if self.handle == 0 { gl::BACK } else { gl::COLOR_ATTACHMENT0 + i as GLenum };
+        let draw_buffer = if target.handle == 0 { gl::BACK } else { gl::COLOR_ATTACHMENT0 + i as GLenum };
 
-      // generate all the required textures with the correct formats
-      gl::GenTextures((textures.len()) as GLint, textures.as_mut_ptr());
+        // depth and stencil are copied along with the first color attachment only
+        let bits = gl::COLOR_BUFFER_BIT | if i == 0 { depth_stencil_bits } else { 0 };
 
-      // color textures
-      if color_formats.is_empty() {
-        gl::DrawBuffer(gl::NONE);
-      } else {
-        for (i, (format, texture)) in color_formats.iter().zip(&textures).enumerate() {
-          gl::BindTexture(target, *texture);
-          create_texture::<L, D>(target, size, mipmaps, *format, &Default::default()).map_err(FramebufferError::TextureError)?;
-          gl::FramebufferTexture(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0 + i as GLenum, *texture, 0);
+        gl::ReadBuffer(read_buffer);
+        state::set_draw_buffers(&[draw_buffer]);
+        gl::BlitFramebuffer(sx0, sy0, sx1, sy1, dx0, dy0, dx1, dy1, bits, filter);
+      }
+
+      if color_nb == 0 && depth_stencil_bits != 0 {
+        gl::BlitFramebuffer(sx0, sy0, sx1, sy1, dx0, dy0, dx1, dy1, depth_stencil_bits, filter);
+      }
+
+      // restore the read and draw buffers we might have altered
+      gl::ReadBuffer(if self.handle == 0 { gl::BACK } else { gl::COLOR_ATTACHMENT0 });
+      set_draw_buffers(target);
+
+      state::bind_framebuffer(gl::READ_FRAMEBUFFER, 0);
+      state::bind_framebuffer(gl::DRAW_FRAMEBUFFER, 0);
+    }
+
+    Ok(())
+  }
+
+  // Read back a region of an attachment of the framebuffer.
+  //
+  // Rows are returned bottom to top, as OpenGL stores them, unless flip_y is set. The region must
+  // fit in the part of the framebuffer currently rendered into (see `viewport_size`).
+  pub fn read_pixels<P>(&self, attachment: ReadAttachment, region: Rect, flip_y: bool) -> ::std::result::Result<Vec<P::RawEncoding>, ReadPixelsError> where P: Pixel, P::RawEncoding: Copy {
+    let pf = P::pixel_format();
+    let (format, _, ty) = gl_pixel_format(pf).unwrap();
+    let components = pixel_components(pf);
+    let (w, h) = self.viewport_size();
+    let fits = |off: u32, len: u32, max: u32| off.checked_add(len).map_or(false, |end| end <= max);
+
+    if !fits(region.x, region.w, w) || !fits(region.y, region.h, h) {
+      return Err(ReadPixelsError::RegionOutOfBounds(region));
+    }
+
+    if self.samples.is_some() {
+      return Err(ReadPixelsError::Multisampled);
+    }
+
+    let read_buffer = match attachment {
+      ReadAttachment::Color(i) => {
+        if !is_color_pixel(pf) {
+          return Err(ReadPixelsError::IncompatiblePixelFormat(attachment));
         }
 
-        // specify the list of color buffers to draw to
-        let color_buf_nb = color_formats.len() as GLsizei;
-        let color_buffers: Vec<_> = (gl::COLOR_ATTACHMENT0..gl::COLOR_ATTACHMENT0 + color_buf_nb as GLenum).collect();
+        if !((self.handle == 0 && i == 0) || i < self.color_formats.len()) {
+          return Err(ReadPixelsError::MissingColorAttachment(i));
+        }
 
-        gl::DrawBuffers(color_buf_nb, color_buffers.as_ptr());
+        if self.handle == 0 { gl::BACK } else { gl::COLOR_ATTACHMENT0 + i as GLenum }
+      },
+      ReadAttachment::Depth => {
+        if !is_depth_pixel(pf) {
+          return Err(ReadPixelsError::IncompatiblePixelFormat(attachment));
+        }
+
+        if !self.has_depth() {
+          return Err(ReadPixelsError::MissingDepthAttachment);
+        }
+
+        gl::NONE
       }
+    };
 
-      // depth texture, if exists
-      if let Some(format) = depth_format {
-        let texture = textures.pop().unwrap();
+    if region.w == 0 || region.h == 0 {
+      return Ok(Vec::new());
+    }
 
-        gl::BindTexture(target, texture);
-        create_texture::<L, D>(target, size, mipmaps, format, &Default::default()).map_err(FramebufferError::TextureError)?;
-        gl::FramebufferTexture(gl::FRAMEBUFFER, gl::DEPTH_ATTACHMENT, texture, 0);
+    let row_len = region.w as usize * components;
+    let len = row_len * region.h as usize;
+    let mut pixels: Vec<P::RawEncoding> = zeroed_texels(len);
 
-        depth_texture = Some(texture);
-      } else {
-        let mut renderbuffer: GLuint = 0;
+    unsafe {
+      state::bind_framebuffer(gl::READ_FRAMEBUFFER, self.handle);
 
-        gl::GenRenderbuffers(1, &mut renderbuffer);
-        gl::BindRenderbuffer(gl::RENDERBUFFER, renderbuffer);
-        gl::RenderbufferStorage(gl::RENDERBUFFER, gl::DEPTH_COMPONENT32F, D::width(size) as GLsizei, D::height(size) as GLsizei);
-        gl::BindRenderbuffer(gl::RENDERBUFFER, 0);
+      if read_buffer != gl::NONE {
+        gl::ReadBuffer(read_buffer);
+      }
 
-        gl::FramebufferRenderbuffer(gl::FRAMEBUFFER, gl::DEPTH_ATTACHMENT, gl::RENDERBUFFER, renderbuffer);
+      set_pack_alignment(pf);
 
-        depth_renderbuffer = Some(renderbuffer);
+      gl::ReadPixels(region.x as GLint, region.y as GLint, region.w as GLsizei, region.h as GLsizei, format, ty, pixels.as_mut_ptr() as *mut c_void);
+
+      gl::ReadBuffer(if self.handle == 0 { gl::BACK } else { gl::COLOR_ATTACHMENT0 });
+      state::bind_framebuffer(gl::READ_FRAMEBUFFER, 0);
+    }
+
+    if flip_y {
+      let mut flipped = Vec::with_capacity(len);
+
+      for row in pixels.chunks(row_len).rev() {
+        flipped.extend_from_slice(row);
       }
 
-      gl::BindTexture(target, 0);
+      Ok(flipped)
+    } else {
+      Ok(pixels)
+    }
+  }
 
-      let mut gl_framebuffer = GLFramebuffer {
-        handle: framebuffer,
-        renderbuffer: depth_renderbuffer,
-        w: D::width(size),
-        h: D::height(size)
-      };
+  // Resolve a multisampled framebuffer into a single-sample one of the same size.
+  //
+  // The depth and stencil attachments are resolved if both framebuffers have them.
+  pub fn resolve(&self, target: &GLFramebuffer) -> ::std::result::Result<(), BlitError> {
+    let mask = BlitMask {
+      color: true,
+      depth: self.depth_format.is_some() && target.depth_format.is_some(),
+      stencil: self.stencil_format.is_some() && target.stencil_format.is_some()
+    };
+
+    self.blit(target, self.rect(), target.rect(), mask, Filter::Nearest)
+  }
 
-      match get_status() {
-        Some(incomplete) => {
-          gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+  // Tell the driver that the contents of some attachments don’t need to be preserved.
+  //
+  // This uses glInvalidateFramebuffer when ARB_invalidate_subdata is available and is a no-op
+  // otherwise. Missing attachments are ignored.
+  pub fn invalidate(&self, mask: &InvalidateMask) {
+    if !has_extension("GL_ARB_invalidate_subdata") || !gl::InvalidateFramebuffer::is_loaded() {
+      return;
+    }
 
-          Self::free_framebuffer(&mut gl_framebuffer);
+    let mut attachments = Vec::new();
 
-          Err(FramebufferError::Incomplete(incomplete))
-        },
-        None => {
-          gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+    if self.handle == 0 {
+      if !mask.colors.is_empty() {
+        attachments.push(gl::COLOR);
+      }
 
-          let textures = textures.into_iter().map(|t| GLTexture::new(t, target)).collect();
-          let depth_texture = depth_texture.map(|t| GLTexture::new(t, target));
-          Ok((gl_framebuffer, textures, depth_texture))
-        }
+      if mask.depth {
+        attachments.push(gl::DEPTH);
       }
+
+      if mask.stencil {
+        attachments.push(gl::STENCIL);
+      }
+    } else {
+      attachments.extend(mask.colors.iter().filter(|&&i| i < self.color_formats.len()).map(|&i| gl::COLOR_ATTACHMENT0 + i as GLenum));
+
+      if mask.depth && self.depth_format.is_some() {
+        attachments.push(gl::DEPTH_ATTACHMENT);
+      }
+
+      if mask.stencil && self.stencil_format.is_some() {
+        attachments.push(gl::STENCIL_ATTACHMENT);
+      }
+    }
+
+    if attachments.is_empty() {
+      return;
+    }
+
+    unsafe {
+      state::bind_framebuffer(gl::FRAMEBUFFER, self.handle);
+      gl::InvalidateFramebuffer(gl::FRAMEBUFFER, attachments.len() as GLsizei, attachments.as_ptr());
+      state::bind_framebuffer(gl::FRAMEBUFFER, 0);
+    }
+  }
+}
+
+// Rectangular region of a framebuffer, with its origin at the lower-left corner.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Rect {
+  pub x: u32,
+  pub y: u32,
+  pub w: u32,
+  pub h: u32
+}
+
+impl Rect {
+  pub fn new(x: u32, y: u32, w: u32, h: u32) -> Self {
+    Rect {
+      x: x,
+      y: y,
+      w: w,
+      h: h
+    }
+  }
+
+  // Lower-left and upper-right corners, as expected by OpenGL; None if they don’t fit in a GLint.
+  fn corners(&self) -> Option<(GLint, GLint, GLint, GLint)> {
+    let fits = |v: u32| if v <= GLint::max_value() as u32 { Some(v as GLint) } else { None };
+    let x1 = self.x.checked_add(self.w).and_then(&fits)?;
+    let y1 = self.y.checked_add(self.h).and_then(&fits)?;
+
+    Some((fits(self.x)?, fits(self.y)?, x1, y1))
+  }
+}
+
+// Attachment to read pixels from.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ReadAttachment {
+  Color(usize),
+  Depth
+}
+
+// Reasons why pixels cannot be read back from a framebuffer.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ReadPixelsError {
+  // the region doesn’t fit in the part of the framebuffer rendered into
+  RegionOutOfBounds(Rect),
+  // multisampled framebuffers must be resolved before being read back
+  Multisampled,
+  // the pixel format doesn’t match the kind of attachment (color or depth)
+  IncompatiblePixelFormat(ReadAttachment),
+  // the color attachment to read doesn’t exist
+  MissingColorAttachment(usize),
+  // the framebuffer has no depth attachment to read
+  MissingDepthAttachment
+}
+
+// Buffers to copy when blitting.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct BlitMask {
+  pub color: bool,
+  pub depth: bool,
+  pub stencil: bool
+}
+
+impl BlitMask {
+  pub fn all() -> Self {
+    BlitMask {
+      color: true,
+      depth: true,
+      stencil: true
+    }
+  }
+}
+
+// Attachments which contents can be discarded with `GLFramebuffer::invalidate`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct InvalidateMask {
+  // indices of the color attachments; on the default framebuffer, any index stands for the color
+  // buffer
+  pub colors: Vec<usize>,
+  pub depth: bool,
+  pub stencil: bool
+}
+
+impl InvalidateMask {
+  // Depth and stencil only, typically not needed once a pass is done.
+  pub fn depth_stencil() -> Self {
+    InvalidateMask {
+      colors: Vec::new(),
+      depth: true,
+      stencil: true
+    }
+  }
+}
+
+// Reasons why two framebuffers cannot be blitted.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum BlitError {
+  // the color attachments at the given index have incompatible formats
+  IncompatibleColorFormats(usize, GLenum, GLenum),
+  // the depth attachments are missing or have different formats
+  IncompatibleDepthFormats(Option<GLenum>, Option<GLenum>),
+  // the stencil attachments are missing or have different formats
+  IncompatibleStencilFormats(Option<GLenum>, Option<GLenum>),
+  // depth, stencil and integer color blits only support nearest filtering
+  LinearFilter,
+  // blits from multisampled framebuffers cannot scale
+  MultisampleScaling,
+  // multisampled framebuffers cannot be blitted into
+  MultisampleDestination,
+  // the corners of the rectangle don’t fit in OpenGL coordinates
+  RectOverflow(Rect)
+}
+
+// Creation options for framebuffers that cannot be expressed through `Framebuffer::new`.
+#[derive(Clone, Debug, Default)]
+pub struct FramebufferOptions {
+  pub samples: Option<u32>, // number of samples per pixel; None for a single-sample framebuffer
+  pub depth_stencil: Option<DepthStencilAttachment>, // depth-stencil or stencil-only attachment
+  pub implicit_depth: ImplicitDepth, // depth renderbuffer to create when there’s no depth slot
+  pub attachment: AttachmentTarget, // part of the color and depth textures to render into
+  pub layers: Option<u32> // number of layers (cubemaps for cubemap arrays) of layered textures; None for one
+}
+
+// Part of a texture a framebuffer renders into.
+#[derive(Clone, Copy, Debug)]
+pub enum AttachmentTarget {
+  // a whole mipmap level; layered textures are attached with all their layers
+  Level(usize),
+  // a mipmap level of a face of a cubemap
+  Face(CubeFace, usize),
+  // a mipmap level of a layer of a layered or 3D texture; (layer, level)
+  Layer(usize, usize)
+}
+
+impl AttachmentTarget {
+  pub fn level(&self) -> usize {
+    match *self {
+      AttachmentTarget::Level(level) | AttachmentTarget::Face(_, level) | AttachmentTarget::Layer(_, level) => level
+    }
+  }
+}
+
+// CubeFace doesn’t implement PartialEq, hence the manual implementation
+impl PartialEq for AttachmentTarget {
+  fn eq(&self, rhs: &Self) -> bool {
+    match (*self, *rhs) {
+      (AttachmentTarget::Level(a), AttachmentTarget::Level(b)) => a == b,
+      (AttachmentTarget::Face(fa, a), AttachmentTarget::Face(fb, b)) => from_cube_face(fa) == from_cube_face(fb) && a == b,
+      (AttachmentTarget::Layer(la, a), AttachmentTarget::Layer(lb, b)) => la == lb && a == b,
+      _ => false
+    }
+  }
+}
+
+impl Eq for AttachmentTarget {}
+
+impl Default for AttachmentTarget {
+  fn default() -> Self {
+    AttachmentTarget::Level(0)
+  }
+}
+
+// Depth renderbuffer created for framebuffers without depth slot.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ImplicitDepth {
+  // no depth at all; such framebuffers reject depth-tested render commands
+  None,
+  Depth16,
+  Depth24,
+  Depth32F
+}
+
+impl Default for ImplicitDepth {
+  fn default() -> Self {
+    ImplicitDepth::Depth32F
+  }
+}
+
+// Formats of depth-stencil and stencil-only attachments.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DepthStencilFormat {
+  Depth24Stencil8,
+  Depth32FStencil8,
+  // stencil-only; as a texture, it requires OpenGL 4.4 or ARB_texture_stencil8
+  Stencil8
+}
+
+// Depth-stencil or stencil-only attachment of a framebuffer.
+//
+// Depth-stencil attachments replace the depth attachment, so they cannot be used along with a depth
+// slot. Stencil-only attachments are attached next to the depth attachment.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DepthStencilAttachment {
+  // texture, that can be sampled afterwards through GLFramebuffer::stencil_texture
+  Texture(DepthStencilFormat),
+  Renderbuffer(DepthStencilFormat)
+}
+
+impl DepthStencilAttachment {
+  pub fn format(&self) -> DepthStencilFormat {
+    match *self {
+      DepthStencilAttachment::Texture(format) | DepthStencilAttachment::Renderbuffer(format) => format
     }
   }
 
+  // Does the attachment hold depth?
+  pub fn has_depth(&self) -> bool {
+    self.format() != DepthStencilFormat::Stencil8
+  }
+}
+
+// Reasons why a framebuffer is incomplete, as reported by glCheckFramebufferStatus.
+//
+// The attachment points (e.g. COLOR_ATTACHMENT1) are given when they could be found out.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Incompleteness {
+  Undefined,
+  // attachment point of the first attachment with an empty image
+  IncompleteAttachment(Option<GLenum>),
+  MissingAttachment,
+  // attachment point selected as draw buffer but without any image attached
+  IncompleteDrawBuffer(Option<GLenum>),
+  // attachment point selected as read buffer but without any image attached
+  IncompleteReadBuffer(Option<GLenum>),
+  Unsupported,
+  IncompleteMultisample,
+  IncompleteLayerTargets,
+  // unknown status, as returned by glCheckFramebufferStatus
+  Unknown(GLenum)
+}
+
+impl Incompleteness {
+  fn describe(&self) -> String {
+    let at = |point: Option<GLenum>| point.map(|p| format!(" (attachment point {})", p)).unwrap_or_default();
+
+    match *self {
+      Incompleteness::Undefined => String::from("framebuffer undefined"),
+      Incompleteness::IncompleteAttachment(point) => format!("incomplete attachment{}", at(point)),
+      Incompleteness::MissingAttachment => String::from("incomplete missing attachment"),
+      Incompleteness::IncompleteDrawBuffer(point) => format!("incomplete draw buffer{}", at(point)),
+      Incompleteness::IncompleteReadBuffer(point) => format!("incomplete read buffer{}", at(point)),
+      Incompleteness::Unsupported => String::from("unsupported"),
+      Incompleteness::IncompleteMultisample => String::from("incomplete multisample"),
+      Incompleteness::IncompleteLayerTargets => String::from("incomplete layer targets"),
+      Incompleteness::Unknown(status) => format!("unknown status {}", status)
+    }
+  }
+}
+
+// Errors that can occur while creating a framebuffer with `new_framebuffer_with`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum GLFramebufferError {
+  TextureError(TextureError),
+  Incomplete(Incompleteness),
+  // error reported by luminance, already turned into a message
+  Other(String),
+  UnsupportedSamples(u32),
+  UnsupportedMultisampleTarget,
+  ConflictingDepthAttachments,
+  UnsupportedDepthStencilTarget,
+  // stencil-only textures are not supported by the context
+  UnsupportedStencilTexture,
+  UnsupportedAttachmentTarget(AttachmentTarget),
+  // the mipmap level or layer of the attachment target doesn’t exist in the textures
+  AttachmentOutOfBounds(AttachmentTarget),
+  // expected and provided numbers of existing color textures
+  ColorTextureCount(usize, usize),
+  // expected and provided values of an existing texture
+  TextureTargetMismatch(GLenum, GLenum),
+  TextureSizeMismatch((u32, u32, u32), (u32, u32, u32)),
+  TextureLevelCount(usize, usize),
+  TextureFormatMismatch(GLenum, GLenum),
+  // the framebuffer renders into existing textures, which it cannot reallocate
+  BorrowedAttachments
+}
+
+impl From<FramebufferError> for GLFramebufferError {
+  fn from(e: FramebufferError) -> Self {
+    match e {
+      FramebufferError::TextureError(e) => GLFramebufferError::TextureError(e),
+      FramebufferError::Incomplete(e) => GLFramebufferError::Other(e)
+    }
+  }
+}
+
+impl From<GLFramebufferError> for FramebufferError {
+  fn from(e: GLFramebufferError) -> Self {
+    match e {
+      GLFramebufferError::TextureError(e) => FramebufferError::TextureError(e),
+      GLFramebufferError::Incomplete(incompleteness) => FramebufferError::Incomplete(incompleteness.describe()),
+      GLFramebufferError::Other(e) => FramebufferError::Incomplete(e),
+      GLFramebufferError::UnsupportedSamples(samples) => FramebufferError::Incomplete(format!("unsupported number of samples: {}", samples)),
+      GLFramebufferError::UnsupportedMultisampleTarget => FramebufferError::Incomplete(String::from("multisampling is only supported for flat 2D framebuffers")),
+      GLFramebufferError::ConflictingDepthAttachments => FramebufferError::Incomplete(String::from("both a depth slot and a depth-stencil attachment were requested")),
+      GLFramebufferError::UnsupportedDepthStencilTarget => FramebufferError::Incomplete(String::from("depth-stencil textures are only supported for flat 2D framebuffers")),
+      GLFramebufferError::UnsupportedStencilTexture => FramebufferError::Incomplete(String::from("stencil-only textures require OpenGL 4.4 or ARB_texture_stencil8")),
+      GLFramebufferError::UnsupportedAttachmentTarget(attachment) => FramebufferError::Incomplete(format!("unsupported attachment target: {:?}", attachment)),
+      GLFramebufferError::AttachmentOutOfBounds(attachment) => FramebufferError::Incomplete(format!("attachment target out of the textures’ bounds: {:?}", attachment)),
+      GLFramebufferError::ColorTextureCount(expected, provided) => FramebufferError::Incomplete(format!("expected {} color textures, got {}", expected, provided)),
+      GLFramebufferError::TextureTargetMismatch(expected, provided) => FramebufferError::Incomplete(format!("expected texture target {}, got {}", expected, provided)),
+      GLFramebufferError::TextureSizeMismatch(expected, provided) => FramebufferError::Incomplete(format!("expected texture size {:?}, got {:?}", expected, provided)),
+      GLFramebufferError::TextureLevelCount(expected, provided) => FramebufferError::Incomplete(format!("expected {} texture mipmap levels, got {}", expected, provided)),
+      GLFramebufferError::TextureFormatMismatch(expected, provided) => FramebufferError::Incomplete(format!("expected texture format {}, got {}", expected, provided)),
+      GLFramebufferError::BorrowedAttachments => FramebufferError::Incomplete(String::from("cannot reallocate borrowed attachments"))
+    }
+  }
+}
+
+type BuiltFramebuffer = (GLFramebuffer, Vec<GLTexture>, Option<GLTexture>);
+
+// Parameters of a framebuffer to build that luminance cannot carry.
+struct FramebufferRequest {
+  options: FramebufferOptions,
+  existing_colors: Option<Vec<GLTexture>>, // borrowed copies of the existing color textures
+  existing_depth: Option<GLTexture> // borrowed copy of the existing depth texture
+}
+
+// What goes between `new_framebuffer_requested` and `new_framebuffer`.
+enum Handover {
+  Request(FramebufferRequest),
+  Failed(GLFramebufferError)
+}
+
+// luminance’s Framebuffer can only be built by Framebuffer::new, which calls new_framebuffer
+// without any room for extra parameters. The request is then handed over through this per-thread
+// slot, and the typed error, if any, is handed back the same way. The mipmaps are the ones
+// Framebuffer::new passes to new_framebuffer.
+thread_local!(static FRAMEBUFFER_HANDOVER: RefCell<Option<Handover>> = RefCell::new(None));
+
+// Empty the handover slot when dropped, so that nothing is left behind, even on panic.
+struct HandoverGuard;
+
+impl Drop for HandoverGuard {
+  fn drop(&mut self) {
+    FRAMEBUFFER_HANDOVER.with(|handover| *handover.borrow_mut() = None);
+  }
+}
+
+// Build a luminance framebuffer out of a request.
+fn new_framebuffer_requested<L, D, CS, DS>(size: D::Size, mipmaps: usize, request: FramebufferRequest) -> ::std::result::Result<Framebuffer<L, D, CS, DS>, GLFramebufferError>
+    where L: Layerable,
+          D: Dimensionable,
+          D::Size: Copy,
+          CS: ColorSlot<GL33, L, D>,
+          DS: DepthSlot<GL33, L, D> {
+  let _guard = HandoverGuard;
+
+  FRAMEBUFFER_HANDOVER.with(|handover| *handover.borrow_mut() = Some(Handover::Request(request)));
+
+  Framebuffer::new(size, mipmaps).map_err(|e| {
+    match FRAMEBUFFER_HANDOVER.with(|handover| handover.borrow_mut().take()) {
+      Some(Handover::Failed(e)) => e,
+      _ => GLFramebufferError::from(e)
+    }
+  })
+}
+
+// Create a new framebuffer with additional creation options.
+pub fn new_framebuffer_with<L, D, CS, DS>(size: D::Size, mipmaps: usize, options: &FramebufferOptions) -> ::std::result::Result<Framebuffer<L, D, CS, DS>, GLFramebufferError>
+    where L: Layerable,
+          D: Dimensionable,
+          D::Size: Copy,
+          CS: ColorSlot<GL33, L, D>,
+          DS: DepthSlot<GL33, L, D> {
+  let request = FramebufferRequest {
+    options: options.clone(),
+    existing_colors: None,
+    existing_depth: None
+  };
+
+  new_framebuffer_requested(size, mipmaps, request)
+}
+
+// Framebuffer rendering into existing textures, created with `new_framebuffer_from_textures`.
+//
+// It derefs to a `Framebuffer`, and cannot outlive the textures it renders into.
+pub struct TextureFramebuffer<'a, L, D, CS, DS>
+    where L: Layerable,
+          D: Dimensionable,
+          D::Size: Copy,
+          CS: ColorSlot<GL33, L, D>,
+          DS: DepthSlot<GL33, L, D> {
+  framebuffer: Framebuffer<L, D, CS, DS>,
+  _textures: PhantomData<&'a GLTexture>
+}
+
+impl<'a, L, D, CS, DS> Deref for TextureFramebuffer<'a, L, D, CS, DS>
+    where L: Layerable,
+          D: Dimensionable,
+          D::Size: Copy,
+          CS: ColorSlot<GL33, L, D>,
+          DS: DepthSlot<GL33, L, D> {
+  type Target = Framebuffer<L, D, CS, DS>;
+
+  fn deref(&self) -> &Self::Target {
+    &self.framebuffer
+  }
+}
+
+impl<'a, L, D, CS, DS> TextureFramebuffer<'a, L, D, CS, DS>
+    where L: Layerable,
+          D: Dimensionable,
+          D::Size: Copy,
+          CS: ColorSlot<GL33, L, D>,
+          DS: DepthSlot<GL33, L, D> {
+  // Backend representation of the framebuffer, to retarget or invalidate it.
+  //
+  // The framebuffer itself is never handed out mutably, so that it cannot be moved out and outlive
+  // the textures.
+  pub fn repr_mut(&mut self) -> &mut GLFramebuffer {
+    &mut self.framebuffer.repr
+  }
+}
+
+// Create a new framebuffer that renders into existing textures instead of creating its own.
+//
+// The color textures must match the color slot in target, size, mipmap levels and format. The
+// depth texture is optional: if missing, the depth slot is handled as usual; if provided without
+// depth slot, it’s still attached, which is handy to share a depth texture between several
+// framebuffers.
+//
+// The existing textures are borrowed by the framebuffer, which doesn’t free them.
+pub fn new_framebuffer_from_textures<'a, L, D, CS, DS>(size: D::Size, mipmaps: usize, color_textures: &[&'a GLTexture], depth_texture: Option<&'a GLTexture>, options: &FramebufferOptions) -> ::std::result::Result<TextureFramebuffer<'a, L, D, CS, DS>, GLFramebufferError>
+    where L: Layerable,
+          D: Dimensionable,
+          D::Size: Copy,
+          CS: ColorSlot<GL33, L, D>,
+          DS: DepthSlot<GL33, L, D> {
+  let borrow = |texture: &GLTexture| GLTexture::borrowed(texture.handle, texture.target);
+  let request = FramebufferRequest {
+    options: options.clone(),
+    existing_colors: Some(color_textures.iter().map(|texture| borrow(texture)).collect()),
+    existing_depth: depth_texture.map(&borrow)
+  };
+
+  new_framebuffer_requested(size, mipmaps, request).map(|framebuffer| {
+    TextureFramebuffer {
+      framebuffer: framebuffer,
+      _textures: PhantomData
+    }
+  })
+}
+
+impl HasFramebuffer for GL33 {
+  type Framebuffer = GLFramebuffer;
+
+  fn new_framebuffer<L, D, CS, DS>(size: D::Size, mipmaps: usize) -> Result<(Self::Framebuffer, Vec<Self::ATexture>, Option<Self::ATexture>)>
+    where L: Layerable,
+          D: Dimensionable,
+          D::Size: Copy,
+          CS: ColorSlot<Self, L, D>,
+          DS: DepthSlot<Self, L, D> {
+    let request = match FRAMEBUFFER_HANDOVER.with(|handover| handover.borrow_mut().take()) {
+      Some(Handover::Request(request)) => request,
+      _ => return build_framebuffer::<L, D, CS, DS>(size, mipmaps, &Default::default(), None, None).map_err(FramebufferError::from)
+    };
+
+    let built = build_framebuffer::<L, D, CS, DS>(size, mipmaps, &request.options, request.existing_colors.as_ref().map(|t| t.as_slice()), request.existing_depth.as_ref());
+
+    built.map_err(|e| {
+      FRAMEBUFFER_HANDOVER.with(|handover| *handover.borrow_mut() = Some(Handover::Failed(e.clone())));
+      FramebufferError::from(e)
+    })
+  }
+
   fn free_framebuffer(framebuffer: &mut Self::Framebuffer) {
     unsafe {
       if let Some(renderbuffer) = framebuffer.renderbuffer {
         gl::DeleteRenderbuffers(1, &renderbuffer);
       }
 
+      if let Some(ref texture) = framebuffer.stencil_texture {
+        gl::DeleteTextures(1, &texture.handle);
+        state::forget_texture(texture.handle);
+      }
+
+      if let Some(renderbuffer) = framebuffer.stencil_renderbuffer {
+        gl::DeleteRenderbuffers(1, &renderbuffer);
+      }
+
       if framebuffer.handle != 0 {
         gl::DeleteFramebuffers(1, &framebuffer.handle);
+        state::forget_framebuffer(framebuffer.handle);
       }
     }
   }
@@ -126,24 +850,760 @@ impl HasFramebuffer for GL33 {
       handle: 0,
       renderbuffer: None,
       w: D::width(size),
-      h: D::height(size)
+      h: D::height(size),
+      samples: None,
+      color_formats: Vec::new(),
+      depth_format: None,
+      stencil_format: None,
+      stencil_texture: None,
+      stencil_renderbuffer: None,
+      texture_target: gl::NONE,
+      color_textures: Vec::new(),
+      depth_texture: None,
+      attachment: AttachmentTarget::default(),
+      borrows_textures: false
+    }
+  }
+}
+
+// Build a framebuffer, creating its textures unless existing ones are provided.
+fn build_framebuffer<L, D, CS, DS>(size: D::Size, mipmaps: usize, options: &FramebufferOptions, existing_colors: Option<&[GLTexture]>, existing_depth: Option<&GLTexture>) -> ::std::result::Result<BuiltFramebuffer, GLFramebufferError>
+    where L: Layerable,
+          D: Dimensionable,
+          D::Size: Copy,
+          CS: ColorSlot<GL33, L, D>,
+          DS: DepthSlot<GL33, L, D> {
+  let mut framebuffer: GLuint = 0;
+  let color_formats = CS::color_formats();
+  let depth_format = DS::depth_format();
+  let target = match options.samples {
+    Some(samples) => {
+      check_samples(samples)?;
+
+      match (L::layering(), D::dim()) {
+        (Layering::Flat, Dim::Dim2) => gl::TEXTURE_2D_MULTISAMPLE,
+        _ => return Err(GLFramebufferError::UnsupportedMultisampleTarget)
+      }
+    },
+    None => to_target(L::layering(), D::dim())
+  };
+  let depth_stencil = options.depth_stencil;
+  let combined_depth_stencil = depth_stencil.map_or(false, |ds| ds.has_depth());
+
+  if combined_depth_stencil && depth_format.is_some() {
+    return Err(GLFramebufferError::ConflictingDepthAttachments);
+  }
+
+  check_attachment_target(target, options.attachment)?;
+
+  if let Some(DepthStencilAttachment::Texture(format)) = depth_stencil {
+    match (L::layering(), D::dim()) {
+      (Layering::Flat, Dim::Dim2) => (),
+      _ => return Err(GLFramebufferError::UnsupportedDepthStencilTarget)
+    }
+
+    // stencil-only textures need OpenGL 4.4 or ARB_texture_stencil8; renderbuffers are fine
+    if format == DepthStencilFormat::Stencil8 && !has_version(4, 4) && !has_extension("GL_ARB_texture_stencil8") {
+      return Err(GLFramebufferError::UnsupportedStencilTexture);
+    }
+  }
+
+  // check the existing textures before creating anything
+  if let Some(existing) = existing_colors {
+    if existing.len() != color_formats.len() {
+      return Err(GLFramebufferError::ColorTextureCount(color_formats.len(), existing.len()));
+    }
+
+    for (format, texture) in color_formats.iter().zip(existing) {
+      check_existing_texture::<D>(texture, target, size, mipmaps, gl_pixel_format(*format).map(|(_, iformat, _)| iformat))?;
+    }
+  }
+
+  let existing_depth_iformat = match existing_depth {
+    Some(_) if combined_depth_stencil => return Err(GLFramebufferError::ConflictingDepthAttachments),
+    Some(texture) => Some(check_existing_texture::<D>(texture, target, size, mipmaps, depth_format.and_then(gl_pixel_format).map(|(_, iformat, _)| iformat))?),
+    None => None
+  };
+
+  let mut textures: Vec<(GLuint, bool)> = Vec::with_capacity(color_formats.len()); // (handle, owned)
+  let mut depth_texture: Option<(GLuint, bool)> = None;
+  let mut depth_renderbuffer: Option<GLuint> = None;
+
+  unsafe {
+    gl::GenFramebuffers(1, &mut framebuffer);
+
+    state::bind_framebuffer(gl::FRAMEBUFFER, framebuffer);
+
+    // color textures
+    if color_formats.is_empty() {
+      state::set_draw_buffers(&[gl::NONE]);
+    } else {
+      for (i, format) in color_formats.iter().enumerate() {
+        let texture = match existing_colors {
+          Some(existing) => (existing[i].handle, false),
+          None => match create_attachment_texture::<L, D>(target, size, mipmaps, options, *format) {
+            Ok(texture) => (texture, true),
+            Err(e) => {
+              discard_framebuffer(framebuffer, &textures);
+              return Err(GLFramebufferError::TextureError(e));
+            }
+          }
+        };
+
+        attach_texture(gl::COLOR_ATTACHMENT0 + i as GLenum, texture.0, options.attachment);
+        textures.push(texture);
+      }
+
+      // specify the list of color buffers to draw to
+      let color_buffers: Vec<_> = (0..color_formats.len()).map(|i| gl::COLOR_ATTACHMENT0 + i as GLenum).collect();
+
+      state::set_draw_buffers(&color_buffers);
+    }
+
+    // depth texture, if exists
+    let depth_iformat = if let Some(texture) = existing_depth {
+      attach_texture(gl::DEPTH_ATTACHMENT, texture.handle, options.attachment);
+
+      depth_texture = Some((texture.handle, false));
+
+      existing_depth_iformat
+    } else if let Some(format) = depth_format {
+      let texture = match create_attachment_texture::<L, D>(target, size, mipmaps, options, format) {
+        Ok(texture) => texture,
+        Err(e) => {
+          discard_framebuffer(framebuffer, &textures);
+          return Err(GLFramebufferError::TextureError(e));
+        }
+      };
+
+      attach_texture(gl::DEPTH_ATTACHMENT, texture, options.attachment);
+
+      depth_texture = Some((texture, true));
+
+      gl_pixel_format(format).map(|(_, iformat, _)| iformat)
+    } else if combined_depth_stencil {
+      // the depth-stencil attachment provides the depth
+      None
+    } else if let Some(iformat) = from_implicit_depth(options.implicit_depth) {
+      let mut renderbuffer: GLuint = 0;
+
+      gl::GenRenderbuffers(1, &mut renderbuffer);
+      gl::BindRenderbuffer(gl::RENDERBUFFER, renderbuffer);
+      create_renderbuffer_storage(iformat, D::width(size), D::height(size), options.samples);
+      gl::BindRenderbuffer(gl::RENDERBUFFER, 0);
+
+      gl::FramebufferRenderbuffer(gl::FRAMEBUFFER, gl::DEPTH_ATTACHMENT, gl::RENDERBUFFER, renderbuffer);
+
+      depth_renderbuffer = Some(renderbuffer);
+
+      Some(iformat)
+    } else {
+      None
+    };
+
+    // depth-stencil or stencil-only attachment, if any
+    let (stencil_texture, stencil_renderbuffer) = match depth_stencil {
+      Some(attachment) => create_depth_stencil_attachment(attachment, D::width(size), D::height(size), options.samples),
+      None => (None, None)
+    };
+    let stencil_iformat = depth_stencil.map(|ds| from_depth_stencil_format(ds.format()).0);
+
+    let color_textures = textures.iter().map(|t| t.0).collect();
+
+    let mut gl_framebuffer = GLFramebuffer {
+      handle: framebuffer,
+      renderbuffer: depth_renderbuffer,
+      w: D::width(size),
+      h: D::height(size),
+      samples: options.samples,
+      color_formats: color_formats.iter().filter_map(|f| gl_pixel_format(*f).map(|(_, iformat, _)| iformat)).collect(),
+      depth_format: if combined_depth_stencil { stencil_iformat } else { depth_iformat },
+      stencil_format: stencil_iformat,
+      stencil_texture: stencil_texture,
+      stencil_renderbuffer: stencil_renderbuffer,
+      texture_target: target,
+      color_textures: color_textures,
+      depth_texture: depth_texture.map(|t| t.0),
+      attachment: options.attachment,
+      borrows_textures: existing_colors.is_some() || existing_depth.is_some()
+    };
+
+    match get_status(target) {
+      Some(incomplete) => {
+        state::bind_framebuffer(gl::FRAMEBUFFER, 0);
+
+        // the color and depth textures are not freed along with the framebuffer
+        GL33::free_framebuffer(&mut gl_framebuffer);
+        delete_owned_textures(textures.iter().chain(&depth_texture));
+
+        Err(incomplete)
+      },
+      None => {
+        state::bind_framebuffer(gl::FRAMEBUFFER, 0);
+
+        // existing textures are borrowed, so that they’re not freed twice
+        let to_texture = |(handle, owned)| if owned { GLTexture::new(handle, target) } else { GLTexture::borrowed(handle, target) };
+        let textures = textures.into_iter().map(&to_texture).collect();
+        let depth_texture = depth_texture.map(&to_texture);
+        Ok((gl_framebuffer, textures, depth_texture))
+      }
+    }
+  }
+}
+
+// Delete a framebuffer that failed to be built, along with the textures it created.
+fn discard_framebuffer(framebuffer: GLuint, textures: &[(GLuint, bool)]) {
+  state::bind_framebuffer(gl::FRAMEBUFFER, 0);
+
+  delete_owned_textures(textures);
+
+  unsafe { gl::DeleteFramebuffers(1, &framebuffer) };
+  state::forget_framebuffer(framebuffer);
+}
+
+// Delete the textures created for a framebuffer; borrowed ones are left untouched.
+fn delete_owned_textures<'a, I>(textures: I) where I: IntoIterator<Item = &'a (GLuint, bool)> {
+  for &(texture, owned) in textures {
+    if owned {
+      unsafe { gl::DeleteTextures(1, &texture) };
+      state::forget_texture(texture);
+    }
+  }
+}
+
+// Create a texture used as a framebuffer attachment.
+fn create_attachment_texture<L, D>(target: GLenum, size: D::Size, mipmaps: usize, options: &FramebufferOptions, format: PixelFormat) -> texture::Result<GLuint>
+    where L: Layerable,
+          D: Dimensionable,
+          D::Size: Copy {
+  let mut texture: GLuint = 0;
+
+  unsafe {
+    gl::GenTextures(1, &mut texture);
+    state::bind_texture(target, texture);
+  }
+
+  let created = match options.samples {
+    Some(samples) => create_multisample_texture(D::width(size), D::height(size), samples, format),
+    None => create_texture::<L, D>(target, size, options.layers.unwrap_or(1), mipmaps, format, &Default::default())
+  };
+
+  state::bind_texture(target, 0);
+
+  match created {
+    Ok(()) => Ok(texture),
+    Err(e) => {
+      unsafe { gl::DeleteTextures(1, &texture) };
+      state::forget_texture(texture);
+      Err(e)
+    }
+  }
+}
+
+// Check that an existing texture can be attached to a framebuffer; i.e. it has the right target,
+// size, number of mipmap levels and, if provided, internal format. Return its internal format.
+//
+// The depth of 3D textures is checked against the size; layered textures keep their own number of
+// layers.
+fn check_existing_texture<D>(texture: &GLTexture, target: GLenum, size: D::Size, mipmaps: usize, iformat: Option<GLenum>) -> ::std::result::Result<GLenum, GLFramebufferError>
+    where D: Dimensionable,
+          D::Size: Copy {
+  if texture.target != target {
+    return Err(GLFramebufferError::TextureTargetMismatch(target, texture.target));
+  }
+
+  // level parameters of cubemaps are queried on faces
+  let query_target = if target == gl::TEXTURE_CUBE_MAP { gl::TEXTURE_CUBE_MAP_POSITIVE_X } else { target };
+  let mut w: GLint = 0;
+  let mut h: GLint = 0;
+  let mut d: GLint = 0;
+  let mut texture_iformat: GLint = 0;
+
+  state::bind_texture(target, texture.handle);
+
+  unsafe {
+    gl::GetTexLevelParameteriv(query_target, 0, gl::TEXTURE_WIDTH, &mut w);
+    gl::GetTexLevelParameteriv(query_target, 0, gl::TEXTURE_HEIGHT, &mut h);
+    gl::GetTexLevelParameteriv(query_target, 0, gl::TEXTURE_DEPTH, &mut d);
+    gl::GetTexLevelParameteriv(query_target, 0, gl::TEXTURE_INTERNAL_FORMAT, &mut texture_iformat);
+  }
+
+  // multisample textures have a single level
+  let levels = if target == gl::TEXTURE_2D_MULTISAMPLE { 1 } else { texture_levels(query_target, mipmaps) };
+
+  state::bind_texture(target, 0);
+
+  let expected_depth = if target == gl::TEXTURE_3D { D::depth(size) } else if is_layered_target(target) { d as u32 } else { 1 };
+  let expected_size = (D::width(size), D::height(size), expected_depth);
+  let texture_size = (w as u32, h as u32, d as u32);
+
+  if texture_size != expected_size {
+    return Err(GLFramebufferError::TextureSizeMismatch(expected_size, texture_size));
+  }
+
+  if levels < mipmaps {
+    return Err(GLFramebufferError::TextureLevelCount(mipmaps, levels));
+  }
+
+  match iformat {
+    Some(iformat) if iformat != texture_iformat as GLenum => Err(GLFramebufferError::TextureFormatMismatch(iformat, texture_iformat as GLenum)),
+    _ => Ok(texture_iformat as GLenum)
+  }
+}
+
+// Number of usable mipmap levels of the bound texture, counted up to `max`; levels past
+// TEXTURE_MAX_LEVEL or without storage are not usable.
+fn texture_levels(query_target: GLenum, max: usize) -> usize {
+  let parameter_target = if query_target == gl::TEXTURE_CUBE_MAP_POSITIVE_X { gl::TEXTURE_CUBE_MAP } else { query_target };
+  let mut max_level: GLint = 0;
+
+  unsafe { gl::GetTexParameteriv(parameter_target, gl::TEXTURE_MAX_LEVEL, &mut max_level) };
+
+  (0..max.min(max_level as usize + 1)).take_while(|&level| {
+    let mut w: GLint = 0;
+    unsafe { gl::GetTexLevelParameteriv(query_target, level as GLint, gl::TEXTURE_WIDTH, &mut w) };
+    w > 0
+  }).count()
+}
+
+fn is_layered_target(target: GLenum) -> bool {
+  match target {
+    gl::TEXTURE_1D_ARRAY | gl::TEXTURE_2D_ARRAY | gl::TEXTURE_CUBE_MAP_ARRAY => true,
+    _ => false
+  }
+}
+
+// Attach a part of a texture to the currently bound framebuffer.
+fn attach_texture(attachment_point: GLenum, texture: GLuint, attachment: AttachmentTarget) {
+  unsafe {
+    match attachment {
+      AttachmentTarget::Level(level) => {
+        gl::FramebufferTexture(gl::FRAMEBUFFER, attachment_point, texture, level as GLint)
+      },
+      AttachmentTarget::Face(face, level) => {
+        gl::FramebufferTexture2D(gl::FRAMEBUFFER, attachment_point, from_cube_face(face), texture, level as GLint)
+      },
+      AttachmentTarget::Layer(layer, level) => {
+        gl::FramebufferTextureLayer(gl::FRAMEBUFFER, attachment_point, texture, level as GLint, layer as GLint)
+      }
+    }
+  }
+}
+
+// Check that a part of a texture can be attached given the target of the texture.
+fn check_attachment_target(target: GLenum, attachment: AttachmentTarget) -> ::std::result::Result<(), GLFramebufferError> {
+  let supported = match attachment {
+    AttachmentTarget::Level(level) => level == 0 || target != gl::TEXTURE_2D_MULTISAMPLE,
+    AttachmentTarget::Face(..) => target == gl::TEXTURE_CUBE_MAP,
+    AttachmentTarget::Layer(..) => match target {
+      gl::TEXTURE_3D | gl::TEXTURE_1D_ARRAY | gl::TEXTURE_2D_ARRAY | gl::TEXTURE_CUBE_MAP_ARRAY => true,
+      _ => false
+    }
+  };
+
+  if supported {
+    Ok(())
+  } else {
+    Err(GLFramebufferError::UnsupportedAttachmentTarget(attachment))
+  }
+}
+
+// Check that the mipmap level and layer of an attachment target exist in a texture.
+fn check_attachment_bounds(target: GLenum, texture: GLuint, attachment: AttachmentTarget) -> ::std::result::Result<(), GLFramebufferError> {
+  let level = attachment.level();
+
+  // multisample textures only have the level 0, already checked with the target
+  if target == gl::TEXTURE_2D_MULTISAMPLE {
+    return Ok(());
+  }
+
+  let query_target = if target == gl::TEXTURE_CUBE_MAP { gl::TEXTURE_CUBE_MAP_POSITIVE_X } else { target };
+
+  state::bind_texture(target, texture);
+
+  let levels = texture_levels(query_target, level + 1);
+  let layers = match attachment {
+    AttachmentTarget::Layer(..) if levels > level => {
+      let (param, layer_level) = match target {
+        gl::TEXTURE_1D_ARRAY => (gl::TEXTURE_HEIGHT, 0),
+        gl::TEXTURE_3D => (gl::TEXTURE_DEPTH, level),
+        _ => (gl::TEXTURE_DEPTH, 0)
+      };
+      let mut layers: GLint = 0;
+
+      unsafe { gl::GetTexLevelParameteriv(target, layer_level as GLint, param, &mut layers) };
+
+      layers as usize
+    },
+    _ => 0
+  };
+
+  state::bind_texture(target, 0);
+
+  let in_bounds = levels > level && match attachment {
+    AttachmentTarget::Layer(layer, _) => layer < layers,
+    _ => true
+  };
+
+  if in_bounds {
+    Ok(())
+  } else {
+    Err(GLFramebufferError::AttachmentOutOfBounds(attachment))
+  }
+}
+
+fn from_cube_face(face: CubeFace) -> GLenum {
+  let index = match face {
+    CubeFace::PositiveX => 0,
+    CubeFace::NegativeX => 1,
+    CubeFace::PositiveY => 2,
+    CubeFace::NegativeY => 3,
+    CubeFace::PositiveZ => 4,
+    CubeFace::NegativeZ => 5
+  };
+
+  gl::TEXTURE_CUBE_MAP_POSITIVE_X + index
+}
+
+// Create and attach a depth-stencil or stencil-only attachment to the currently bound framebuffer.
+fn create_depth_stencil_attachment(attachment: DepthStencilAttachment, w: u32, h: u32, samples: Option<u32>) -> (Option<GLTexture>, Option<GLuint>) {
+  let (iformat, format, ty) = from_depth_stencil_format(attachment.format());
+  let attachment_point = if attachment.has_depth() { gl::DEPTH_STENCIL_ATTACHMENT } else { gl::STENCIL_ATTACHMENT };
+
+  unsafe {
+    match attachment {
+      DepthStencilAttachment::Texture(_) => {
+        let mut texture: GLuint = 0;
+        let target = if samples.is_some() { gl::TEXTURE_2D_MULTISAMPLE } else { gl::TEXTURE_2D };
+
+        gl::GenTextures(1, &mut texture);
+        state::bind_texture(target, texture);
+
+        match samples {
+          Some(samples) => gl::TexImage2DMultisample(target, samples as GLsizei, iformat, w as GLsizei, h as GLsizei, gl::TRUE),
+          None => {
+            gl::TexParameteri(target, gl::TEXTURE_MAX_LEVEL, 0);
+            gl::TexParameteri(target, gl::TEXTURE_MIN_FILTER, gl::NEAREST as GLint);
+            gl::TexParameteri(target, gl::TEXTURE_MAG_FILTER, gl::NEAREST as GLint);
+            gl::TexImage2D(target, 0, iformat as GLint, w as GLsizei, h as GLsizei, 0, format, ty, ptr::null());
+          }
+        }
+
+        state::bind_texture(target, 0);
+        gl::FramebufferTexture(gl::FRAMEBUFFER, attachment_point, texture, 0);
+
+        (Some(GLTexture::new(texture, target)), None)
+      },
+      DepthStencilAttachment::Renderbuffer(_) => {
+        let mut renderbuffer: GLuint = 0;
+
+        gl::GenRenderbuffers(1, &mut renderbuffer);
+        gl::BindRenderbuffer(gl::RENDERBUFFER, renderbuffer);
+        create_renderbuffer_storage(iformat, w, h, samples);
+        gl::BindRenderbuffer(gl::RENDERBUFFER, 0);
+
+        gl::FramebufferRenderbuffer(gl::FRAMEBUFFER, attachment_point, gl::RENDERBUFFER, renderbuffer);
+
+        (None, Some(renderbuffer))
+      }
+    }
+  }
+}
+
+// Return the internal sized-format, format and type of a depth-stencil format.
+fn from_depth_stencil_format(format: DepthStencilFormat) -> (GLenum, GLenum, GLenum) {
+  match format {
+    DepthStencilFormat::Depth24Stencil8 => (gl::DEPTH24_STENCIL8, gl::DEPTH_STENCIL, gl::UNSIGNED_INT_24_8),
+    DepthStencilFormat::Depth32FStencil8 => (gl::DEPTH32F_STENCIL8, gl::DEPTH_STENCIL, gl::FLOAT_32_UNSIGNED_INT_24_8_REV),
+    DepthStencilFormat::Stencil8 => (gl::STENCIL_INDEX8, gl::STENCIL_INDEX, gl::UNSIGNED_BYTE)
+  }
+}
+
+fn from_implicit_depth(depth: ImplicitDepth) -> Option<GLenum> {
+  match depth {
+    ImplicitDepth::None => None,
+    ImplicitDepth::Depth16 => Some(gl::DEPTH_COMPONENT16),
+    ImplicitDepth::Depth24 => Some(gl::DEPTH_COMPONENT24),
+    ImplicitDepth::Depth32F => Some(gl::DEPTH_COMPONENT32F)
+  }
+}
+
+// Reallocate the storage of all the mipmap levels of a texture at a new size. The depth of 3D
+// textures and the layers of layered textures are kept.
+fn reallocate_texture(target: GLenum, texture: GLuint, iformat: GLenum, w: u32, h: u32, samples: Option<u32>) {
+  let (format, ty) = gl_transfer_format(iformat).unwrap();
+
+  unsafe {
+    state::bind_texture(target, texture);
+
+    if target == gl::TEXTURE_2D_MULTISAMPLE {
+      gl::TexImage2DMultisample(target, samples.unwrap_or(1) as GLsizei, iformat, w as GLsizei, h as GLsizei, gl::TRUE);
+    } else {
+      let query_target = if target == gl::TEXTURE_CUBE_MAP { gl::TEXTURE_CUBE_MAP_POSITIVE_X } else { target };
+      let mut max_level: GLint = 0;
+      let mut layers: GLint = 0;
+      let mut d: GLint = 0;
+
+      gl::GetTexParameteriv(target, gl::TEXTURE_MAX_LEVEL, &mut max_level);
+      gl::GetTexLevelParameteriv(query_target, 0, gl::TEXTURE_HEIGHT, &mut layers);
+      gl::GetTexLevelParameteriv(query_target, 0, gl::TEXTURE_DEPTH, &mut d);
+
+      // all the levels are reallocated, even past the last meaningful one, so that the mipmap chain
+      // stays consistent; textures of framebuffers always have their maximum level set
+      for level in 0..max_level as u32 + 1 {
+        // every level is at least one texel wide, high and deep
+        let lw = (w >> level).max(1) as GLsizei;
+        let lh = (h >> level).max(1) as GLsizei;
+        let ld = if target == gl::TEXTURE_3D { (d >> level).max(1) } else { d };
+        let level = level as GLint;
+        let iformat = iformat as GLint;
+
+        match target {
+          gl::TEXTURE_1D => gl::TexImage1D(target, level, iformat, lw, 0, format, ty, ptr::null()),
+          gl::TEXTURE_1D_ARRAY => gl::TexImage2D(target, level, iformat, lw, layers, 0, format, ty, ptr::null()),
+          gl::TEXTURE_2D => gl::TexImage2D(target, level, iformat, lw, lh, 0, format, ty, ptr::null()),
+          gl::TEXTURE_CUBE_MAP => {
+            for face in 0..6 {
+              gl::TexImage2D(gl::TEXTURE_CUBE_MAP_POSITIVE_X + face, level, iformat, lw, lw, 0, format, ty, ptr::null());
+            }
+          },
+          _ => gl::TexImage3D(target, level, iformat, lw, lh, ld, 0, format, ty, ptr::null())
+        }
+      }
     }
+
+    state::bind_texture(target, 0);
   }
 }
 
-fn get_status() -> Option<String> {
+fn reallocate_renderbuffer(renderbuffer: GLuint, iformat: GLenum, w: u32, h: u32, samples: Option<u32>) {
+  unsafe {
+    gl::BindRenderbuffer(gl::RENDERBUFFER, renderbuffer);
+    create_renderbuffer_storage(iformat, w, h, samples);
+    gl::BindRenderbuffer(gl::RENDERBUFFER, 0);
+  }
+}
+
+// Create the storage of the currently bound renderbuffer.
+fn create_renderbuffer_storage(iformat: GLenum, w: u32, h: u32, samples: Option<u32>) {
+  unsafe {
+    match samples {
+      Some(samples) => gl::RenderbufferStorageMultisample(gl::RENDERBUFFER, samples as GLsizei, iformat, w as GLsizei, h as GLsizei),
+      None => gl::RenderbufferStorage(gl::RENDERBUFFER, iformat, w as GLsizei, h as GLsizei)
+    }
+  }
+}
+
+// Number of color attachments copied by a blit.
+fn blit_color_nb(src: &GLFramebuffer, dst: &GLFramebuffer) -> usize {
+  // the default framebuffer has a single, implicit color buffer
+  let nb = |fb: &GLFramebuffer| if fb.handle == 0 { 1 } else { fb.color_formats.len() };
+  nb(src).min(nb(dst))
+}
+
+// Check that a blit is legal before issuing it; formats of the default framebuffer are not known and
+// trusted to match.
+fn check_blit(src: &GLFramebuffer, dst: &GLFramebuffer, src_rect: Rect, dst_rect: Rect, mask: BlitMask, filter: Filter) -> ::std::result::Result<(), BlitError> {
+  let linear = match filter {
+    Filter::Linear => true,
+    Filter::Nearest => false
+  };
+  let known = src.handle != 0 && dst.handle != 0;
+
+  if dst.samples.is_some() {
+    return Err(BlitError::MultisampleDestination);
+  }
+
+  // resolves copy samples as they are, so they require identical sizes and formats
+  let resolve = src.samples.is_some();
+
+  if resolve && (src_rect.w != dst_rect.w || src_rect.h != dst_rect.h) {
+    return Err(BlitError::MultisampleScaling);
+  }
+
+  if mask.color && known {
+    for (i, (src_format, dst_format)) in src.color_formats.iter().zip(&dst.color_formats).enumerate() {
+      if is_integer_format(*src_format) != is_integer_format(*dst_format) || (resolve && src_format != dst_format) {
+        return Err(BlitError::IncompatibleColorFormats(i, *src_format, *dst_format));
+      }
+
+      if linear && is_integer_format(*src_format) {
+        return Err(BlitError::LinearFilter);
+      }
+    }
+  }
+
+  if mask.depth || mask.stencil {
+    if linear {
+      return Err(BlitError::LinearFilter);
+    }
+
+    if known && mask.depth && (src.depth_format.is_none() || src.depth_format != dst.depth_format) {
+      return Err(BlitError::IncompatibleDepthFormats(src.depth_format, dst.depth_format));
+    }
+
+    if known && mask.stencil && (src.stencil_format.is_none() || src.stencil_format != dst.stencil_format) {
+      return Err(BlitError::IncompatibleStencilFormats(src.stencil_format, dst.stencil_format));
+    }
+  }
+
+  Ok(())
+}
+
+// Is an internal format an integer one (i.e. not normalized nor floating)?
+pub fn is_integer_format(iformat: GLenum) -> bool {
+  match iformat {
+    gl::R8UI | gl::RG8UI | gl::RGB8UI | gl::RGBA8UI => true,
+    _ => is_signed_integer_format(iformat)
+  }
+}
+
+pub fn is_signed_integer_format(iformat: GLenum) -> bool {
+  match iformat {
+    gl::R8I | gl::RG8I | gl::RGB8I | gl::RGBA8I => true,
+    _ => false
+  }
+}
+
+// Check that the implementation supports the requested number of samples.
+fn check_samples(samples: u32) -> ::std::result::Result<(), GLFramebufferError> {
+  let mut max_samples: GLint = 0;
+  unsafe { gl::GetIntegerv(gl::MAX_SAMPLES, &mut max_samples) };
+
+  if samples == 0 || samples > max_samples as u32 {
+    Err(GLFramebufferError::UnsupportedSamples(samples))
+  } else {
+    Ok(())
+  }
+}
+
+// Reset the draw buffers of a framebuffer to all of its color attachments.
+fn set_draw_buffers(framebuffer: &GLFramebuffer) {
+  if framebuffer.handle == 0 {
+    state::set_draw_buffers(&[gl::BACK]);
+  } else if framebuffer.color_formats.is_empty() {
+    state::set_draw_buffers(&[gl::NONE]);
+  } else {
+    let color_buffers: Vec<_> = (0..framebuffer.color_formats.len()).map(|i| gl::COLOR_ATTACHMENT0 + i as GLenum).collect();
+
+    state::set_draw_buffers(&color_buffers);
+  }
+}
+
+// Check the completeness of the currently bound framebuffer, which textures have the given target.
+fn get_status(target: GLenum) -> Option<GLFramebufferError> {
   let status = unsafe { gl::CheckFramebufferStatus(gl::FRAMEBUFFER) };
 
-  match status {
-    gl::FRAMEBUFFER_COMPLETE => None,
-    gl::FRAMEBUFFER_UNDEFINED => Some(String::from("framebuffer undefined")),
-    gl::FRAMEBUFFER_INCOMPLETE_ATTACHMENT => Some(String::from("incomplete attachment")),
-    gl::FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT => Some(String::from("incomplete missing attachment")),
-    gl::FRAMEBUFFER_INCOMPLETE_DRAW_BUFFER => Some(String::from("incomplete draw buffer")),
-    gl::FRAMEBUFFER_INCOMPLETE_READ_BUFFER => Some(String::from("incomplete read buffer")),
-    gl::FRAMEBUFFER_UNSUPPORTED => Some(String::from("unsupported")),
-    gl::FRAMEBUFFER_INCOMPLETE_MULTISAMPLE => Some(String::from("incomplete multisample")),
-    gl::FRAMEBUFFER_INCOMPLETE_LAYER_TARGETS => Some(String::from("incomplete layer targets")),
-    _ => Some(String::from("unknown"))
+  let incompleteness = match status {
+    gl::FRAMEBUFFER_COMPLETE => return None,
+    gl::FRAMEBUFFER_UNDEFINED => Incompleteness::Undefined,
+    gl::FRAMEBUFFER_INCOMPLETE_ATTACHMENT => Incompleteness::IncompleteAttachment(find_empty_attachment(target)),
+    gl::FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT => Incompleteness::MissingAttachment,
+    gl::FRAMEBUFFER_INCOMPLETE_DRAW_BUFFER => Incompleteness::IncompleteDrawBuffer(find_unattached_draw_buffer()),
+    gl::FRAMEBUFFER_INCOMPLETE_READ_BUFFER => Incompleteness::IncompleteReadBuffer(find_unattached_read_buffer()),
+    gl::FRAMEBUFFER_UNSUPPORTED => Incompleteness::Unsupported,
+    gl::FRAMEBUFFER_INCOMPLETE_MULTISAMPLE => Incompleteness::IncompleteMultisample,
+    gl::FRAMEBUFFER_INCOMPLETE_LAYER_TARGETS => Incompleteness::IncompleteLayerTargets,
+    _ => Incompleteness::Unknown(status)
+  };
+
+  Some(GLFramebufferError::Incomplete(incompleteness))
+}
+
+// Attachment points of the currently bound framebuffer, along with the target of the textures
+// attached to them. Depth-stencil and stencil-only textures are always two-dimensional.
+fn attachment_points(target: GLenum) -> Vec<(GLenum, GLenum)> {
+  let mut max_color_attachments: GLint = 0;
+
+  unsafe { gl::GetIntegerv(gl::MAX_COLOR_ATTACHMENTS, &mut max_color_attachments) };
+
+  let stencil_target = if target == gl::TEXTURE_2D_MULTISAMPLE { target } else { gl::TEXTURE_2D };
+  let mut points: Vec<_> = (0..max_color_attachments as GLenum).map(|i| (gl::COLOR_ATTACHMENT0 + i, target)).collect();
+
+  if is_depth_stencil_attached() {
+    points.push((gl::DEPTH_STENCIL_ATTACHMENT, stencil_target));
+  } else {
+    points.push((gl::DEPTH_ATTACHMENT, target));
+    points.push((gl::STENCIL_ATTACHMENT, stencil_target));
   }
+
+  points
+}
+
+// Whether the same image is attached to both the depth and stencil attachment points.
+fn is_depth_stencil_attached() -> bool {
+  let depth_type = attachment_parameter(gl::DEPTH_ATTACHMENT, gl::FRAMEBUFFER_ATTACHMENT_OBJECT_TYPE);
+  let depth_name = attachment_parameter(gl::DEPTH_ATTACHMENT, gl::FRAMEBUFFER_ATTACHMENT_OBJECT_NAME);
+  let stencil_type = attachment_parameter(gl::STENCIL_ATTACHMENT, gl::FRAMEBUFFER_ATTACHMENT_OBJECT_TYPE);
+  let stencil_name = attachment_parameter(gl::STENCIL_ATTACHMENT, gl::FRAMEBUFFER_ATTACHMENT_OBJECT_NAME);
+
+  depth_type as GLenum != gl::NONE && depth_type == stencil_type && depth_name == stencil_name
+}
+
+fn attachment_parameter(point: GLenum, param: GLenum) -> GLint {
+  let mut value: GLint = 0;
+
+  unsafe { gl::GetFramebufferAttachmentParameteriv(gl::FRAMEBUFFER, point, param, &mut value) };
+
+  value
+}
+
+// Find the first attachment of the currently bound framebuffer which image is empty.
+fn find_empty_attachment(target: GLenum) -> Option<GLenum> {
+  attachment_points(target).into_iter().find(|&(point, target)| {
+    let object_type = attachment_parameter(point, gl::FRAMEBUFFER_ATTACHMENT_OBJECT_TYPE) as GLenum;
+    let name = attachment_parameter(point, gl::FRAMEBUFFER_ATTACHMENT_OBJECT_NAME) as GLuint;
+    let mut w: GLint = 0;
+    let mut h: GLint = 0;
+
+    unsafe {
+      match object_type {
+        gl::RENDERBUFFER => {
+          gl::BindRenderbuffer(gl::RENDERBUFFER, name);
+          gl::GetRenderbufferParameteriv(gl::RENDERBUFFER, gl::RENDERBUFFER_WIDTH, &mut w);
+          gl::GetRenderbufferParameteriv(gl::RENDERBUFFER, gl::RENDERBUFFER_HEIGHT, &mut h);
+          gl::BindRenderbuffer(gl::RENDERBUFFER, 0);
+        },
+        gl::TEXTURE => {
+          let level = attachment_parameter(point, gl::FRAMEBUFFER_ATTACHMENT_TEXTURE_LEVEL);
+          let face = attachment_parameter(point, gl::FRAMEBUFFER_ATTACHMENT_TEXTURE_CUBE_MAP_FACE) as GLenum;
+          let (bind_target, query_target) = if face == 0 { (target, target) } else { (gl::TEXTURE_CUBE_MAP, face) };
+
+          state::bind_texture(bind_target, name);
+          gl::GetTexLevelParameteriv(query_target, level, gl::TEXTURE_WIDTH, &mut w);
+          gl::GetTexLevelParameteriv(query_target, level, gl::TEXTURE_HEIGHT, &mut h);
+          state::bind_texture(bind_target, 0);
+        },
+        _ => return false
+      }
+    }
+
+    w == 0 || h == 0
+  }).map(|(point, _)| point)
+}
+
+fn is_unattached(buffer: GLenum) -> bool {
+  buffer != gl::NONE && attachment_parameter(buffer, gl::FRAMEBUFFER_ATTACHMENT_OBJECT_TYPE) as GLenum == gl::NONE
+}
+
+// Find the first draw buffer of the currently bound framebuffer without any image attached.
+fn find_unattached_draw_buffer() -> Option<GLenum> {
+  let mut max_draw_buffers: GLint = 0;
+
+  unsafe { gl::GetIntegerv(gl::MAX_DRAW_BUFFERS, &mut max_draw_buffers) };
+
+  (0..max_draw_buffers as GLenum).map(|i| {
+    let mut buffer: GLint = 0;
+    unsafe { gl::GetIntegerv(gl::DRAW_BUFFER0 + i, &mut buffer) };
+    buffer as GLenum
+  }).find(|buffer| is_unattached(*buffer))
+}
+
+// Find out whether the read buffer of the currently bound framebuffer has no image attached.
+fn find_unattached_read_buffer() -> Option<GLenum> {
+  let mut buffer: GLint = 0;
+
+  unsafe { gl::GetIntegerv(gl::READ_BUFFER, &mut buffer) };
+
+  if is_unattached(buffer as GLenum) { Some(buffer as GLenum) } else { None }
 }
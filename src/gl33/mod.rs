@@ -1,16 +1,23 @@
 pub mod buffer;
+pub mod extension;
 pub mod framebuffer;
 pub mod pipeline;
 pub mod shader;
+pub mod state;
 pub mod tess;
 pub mod texture;
 pub mod token;
 
 pub use self::buffer::{Buffer, BufferSlice, BufferSliceMut};
-pub use self::framebuffer::Framebuffer;
-pub use self::pipeline::{Pipe, Pipeline, RenderCommand, ShadingCommand};
+pub use self::framebuffer::{AttachmentTarget, BlitError, BlitMask, DepthStencilAttachment, DepthStencilFormat,
+                            Framebuffer, FramebufferOptions, GLFramebufferError, ImplicitDepth, Incompleteness,
+                            InvalidateMask, ReadAttachment, ReadPixelsError, Rect, TextureFramebuffer};
+pub use self::pipeline::{BlendFactor, Blending, ClearError, ClearPolicy, ClearValue, ColorClear, ColorMask,
+                         FaceCulling, Pipe, Pipeline, PipelineError, PipelineOptions, PolygonMode, PolygonOffset,
+                         RenderCommand, RenderState, RenderStateError, ShadingCommand, StencilFace, StencilOp,
+                         StencilTest, Winding};
 pub use self::shader::program::{Program, Uniform};
 pub use self::shader::stage::Stage;
-pub use self::tess::Tess;
-pub use self::texture::Texture;
+pub use self::tess::{Tess, TessError, TessIndices, TessOptions, TessRange, TessRangeError, TessSlice};
+pub use self::texture::{ReadTexelsError, Swizzle, SwizzleComponent, Texture};
 pub use self::token::*;
@@ -1,10 +1,16 @@
 use gl;
 use gl::types::*;
+use gl33::extension::has_extension;
+use gl33::framebuffer::{GLFramebuffer, InvalidateMask, Rect, is_integer_format, is_signed_integer_format};
+use gl33::state;
+use gl33::texture::from_depth_comparison;
 use gl33::token::GL33;
 use luminance::blending;
 use luminance::framebuffer::{ColorSlot, DepthSlot};
 use luminance::pipeline::{self, HasPipeline};
-use luminance::texture::{Dimensionable, Layerable};
+use luminance::texture::{DepthComparison, Dimensionable, Layerable};
+use std::cell::{Cell, RefCell};
+use std::default::Default;
 
 use gl33::shader::program::Program;
 
@@ -13,6 +19,536 @@ pub type Pipe<'a, T> = pipeline::Pipe<'a, GL33, T>;
 pub type ShadingCommand<'a> = pipeline::ShadingCommand<'a, GL33>;
 pub type RenderCommand<'a> = pipeline::RenderCommand<'a, GL33>;
 
+// Value a color attachment is cleared with. Its kind must match the format of the attachment.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ClearValue {
+  Float([f32; 4]),
+  Int([i32; 4]),
+  UInt([u32; 4])
+}
+
+// How the color attachments are cleared at the beginning of a pipeline.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ColorClear {
+  // keep the previous contents
+  Keep,
+  // clear all color attachments with the clear color of the pipeline, converted to integers for
+  // integer attachments
+  ClearColor,
+  // clear each color attachment with its own value, or keep it with None
+  PerAttachment(Vec<Option<ClearValue>>)
+}
+
+// How the framebuffer is cleared at the beginning of a pipeline.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClearPolicy {
+  pub color: ColorClear,
+  // depth clear value, if the depth must be cleared
+  pub depth: Option<f32>,
+  // stencil clear value, if the stencil must be cleared
+  pub stencil: Option<GLint>
+}
+
+impl ClearPolicy {
+  // Keep the previous contents of the framebuffer.
+  pub fn skip() -> Self {
+    ClearPolicy {
+      color: ColorClear::Keep,
+      depth: None,
+      stencil: None
+    }
+  }
+
+  // Clear only the color attachments, with the clear color of the pipeline.
+  pub fn color_only() -> Self {
+    ClearPolicy {
+      color: ColorClear::ClearColor,
+      depth: None,
+      stencil: None
+    }
+  }
+
+  // Clear each color attachment of a framebuffer with its own value, checked against the format of
+  // the attachment, along with the depth and stencil.
+  pub fn per_attachment(framebuffer: &GLFramebuffer, values: Vec<Option<ClearValue>>, depth: Option<f32>, stencil: Option<GLint>) -> Result<Self, ClearError> {
+    let policy = ClearPolicy {
+      color: ColorClear::PerAttachment(values),
+      depth: depth,
+      stencil: stencil
+    };
+
+    policy.check(framebuffer)?;
+
+    Ok(policy)
+  }
+
+  // Check that the policy applies to a framebuffer.
+  pub fn check(&self, framebuffer: &GLFramebuffer) -> Result<(), ClearError> {
+    if let ColorClear::PerAttachment(ref values) = self.color {
+      for (i, value) in values.iter().enumerate() {
+        if let Some(ref value) = *value {
+          let iformat = color_format(framebuffer, i).ok_or(ClearError::MissingColorAttachment(i))?;
+
+          if !is_clear_value_compatible(value, iformat) {
+            return Err(ClearError::IncompatibleClearValue(i, *value));
+          }
+        }
+      }
+    }
+
+    Ok(())
+  }
+}
+
+// Reasons why a clear policy doesn’t apply to a framebuffer.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ClearError {
+  // a value is given for a missing color attachment
+  MissingColorAttachment(usize),
+  // the kind of the value doesn’t match the format of the color attachment
+  IncompatibleClearValue(usize, ClearValue)
+}
+
+impl Default for ClearPolicy {
+  fn default() -> Self {
+    ClearPolicy {
+      color: ColorClear::ClearColor,
+      depth: Some(1.),
+      stencil: None
+    }
+  }
+}
+
+// Options of a pipeline that luminance’s `Pipeline` cannot carry.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PipelineOptions {
+  pub clear: ClearPolicy,
+  // attachments to invalidate at the end of the pipeline, if any
+  pub invalidate: Option<InvalidateMask>
+}
+
+// Color write mask of a color attachment.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ColorMask {
+  pub r: bool,
+  pub g: bool,
+  pub b: bool,
+  pub a: bool
+}
+
+impl ColorMask {
+  pub fn new(r: bool, g: bool, b: bool, a: bool) -> Self {
+    ColorMask {
+      r: r,
+      g: g,
+      b: b,
+      a: a
+    }
+  }
+
+  // Mask all channels out.
+  pub fn none() -> Self {
+    ColorMask::new(false, false, false, false)
+  }
+}
+
+impl Default for ColorMask {
+  fn default() -> Self {
+    ColorMask::new(true, true, true, true)
+  }
+}
+
+// Faces to cull.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum FaceCulling {
+  None,
+  Front,
+  Back,
+  FrontAndBack
+}
+
+impl Default for FaceCulling {
+  fn default() -> Self {
+    FaceCulling::None
+  }
+}
+
+// Winding order of front faces.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Winding {
+  CW,
+  CCW
+}
+
+impl Default for Winding {
+  fn default() -> Self {
+    Winding::CCW
+  }
+}
+
+// Operation applied to the stencil buffer.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum StencilOp {
+  Keep,
+  Zero,
+  Replace,
+  Increment,
+  IncrementWrap,
+  Decrement,
+  DecrementWrap,
+  Invert
+}
+
+// Stencil test and operations of faces of a given orientation.
+#[derive(Clone, Copy, Debug)]
+pub struct StencilFace {
+  // comparison of the reference value with the stored one
+  pub comparison: DepthComparison,
+  pub reference: GLint,
+  // mask applied to both the reference and stored values before comparing them
+  pub read_mask: GLuint,
+  // mask of the bits that can be written
+  pub write_mask: GLuint,
+  // operation when the stencil test fails
+  pub stencil_fail: StencilOp,
+  // operation when the stencil test passes but the depth test fails
+  pub depth_fail: StencilOp,
+  // operation when both tests pass
+  pub depth_pass: StencilOp
+}
+
+impl Default for StencilFace {
+  fn default() -> Self {
+    StencilFace {
+      comparison: DepthComparison::Always,
+      reference: 0,
+      read_mask: !0,
+      write_mask: !0,
+      stencil_fail: StencilOp::Keep,
+      depth_fail: StencilOp::Keep,
+      depth_pass: StencilOp::Keep
+    }
+  }
+}
+
+// Stencil test of front and back faces.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StencilTest {
+  pub front: StencilFace,
+  pub back: StencilFace
+}
+
+impl StencilTest {
+  // Same test and operations for both front and back faces.
+  pub fn both(face: StencilFace) -> Self {
+    StencilTest {
+      front: face,
+      back: face
+    }
+  }
+}
+
+// How polygons are rasterized.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PolygonMode {
+  Fill,
+  Line,
+  Point
+}
+
+impl Default for PolygonMode {
+  fn default() -> Self {
+    PolygonMode::Fill
+  }
+}
+
+// Offset added to the depth of polygons, as in glPolygonOffset.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PolygonOffset {
+  // scale of the maximum depth slope of the polygon
+  pub factor: f32,
+  // scale of the smallest resolvable depth difference
+  pub units: f32
+}
+
+// Blending factor; luminance’s factors along with the constant color ones.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BlendFactor {
+  One,
+  Zero,
+  SrcColor,
+  SrcColorComplement,
+  DestColor,
+  DestColorComplement,
+  SrcAlpha,
+  SrcAlphaComplement,
+  DstAlpha,
+  DstAlphaComplement,
+  SrcAlphaSaturate,
+  // factors using the constant color of RenderState::blend_color
+  ConstantColor,
+  ConstantColorComplement,
+  ConstantAlpha,
+  ConstantAlphaComplement
+}
+
+impl From<blending::Factor> for BlendFactor {
+  fn from(factor: blending::Factor) -> Self {
+    match factor {
+      blending::Factor::One => BlendFactor::One,
+      blending::Factor::Zero => BlendFactor::Zero,
+      blending::Factor::SrcColor => BlendFactor::SrcColor,
+      blending::Factor::SrcColorComplement => BlendFactor::SrcColorComplement,
+      blending::Factor::DestColor => BlendFactor::DestColor,
+      blending::Factor::DestColorComplement => BlendFactor::DestColorComplement,
+      blending::Factor::SrcAlpha => BlendFactor::SrcAlpha,
+      blending::Factor::SrcAlphaComplement => BlendFactor::SrcAlphaComplement,
+      blending::Factor::DstAlpha => BlendFactor::DstAlpha,
+      blending::Factor::DstAlphaComplement => BlendFactor::DstAlphaComplement,
+      blending::Factor::SrcAlphaSaturate => BlendFactor::SrcAlphaSaturate
+    }
+  }
+}
+
+// Blending with separate equations and factors for the RGB and alpha channels.
+#[derive(Clone, Copy, Debug)]
+pub struct Blending {
+  pub rgb_equation: blending::Equation,
+  pub rgb_src: BlendFactor,
+  pub rgb_dst: BlendFactor,
+  pub alpha_equation: blending::Equation,
+  pub alpha_src: BlendFactor,
+  pub alpha_dst: BlendFactor
+}
+
+impl Blending {
+  // Same equation and factors for all channels.
+  pub fn new(equation: blending::Equation, src: BlendFactor, dst: BlendFactor) -> Self {
+    Blending {
+      rgb_equation: equation,
+      rgb_src: src,
+      rgb_dst: dst,
+      alpha_equation: equation,
+      alpha_src: src,
+      alpha_dst: dst
+    }
+  }
+}
+
+impl From<(blending::Equation, blending::Factor, blending::Factor)> for Blending {
+  fn from((equation, src, dst): (blending::Equation, blending::Factor, blending::Factor)) -> Self {
+    Blending::new(equation, src.into(), dst.into())
+  }
+}
+
+// State of a render command that luminance’s `RenderCommand` cannot carry.
+//
+// It’s set with `set_render_state` from the update closure of a render command, or from the one of
+// a shading command to apply it to all of its render commands that don’t set their own. Render
+// commands that don’t get any render state use the default one.
+#[derive(Clone, Debug)]
+pub struct RenderState {
+  // indices of the color attachments to draw to; all of them if None
+  pub draw_buffers: Option<Vec<usize>>,
+  // color write mask of all the color attachments
+  pub color_mask: ColorMask,
+  // color write masks of given color attachments, overriding color_mask
+  pub attachment_color_masks: Vec<(usize, ColorMask)>,
+  pub face_culling: FaceCulling,
+  pub front_face: Winding,
+  // comparison of the depth test, enabled by the render command
+  pub depth_comparison: DepthComparison,
+  // whether the depth test writes the depth of passing fragments
+  pub depth_write: bool,
+  // whether fragments are clamped to the depth range instead of being clipped
+  pub depth_clamp: bool,
+  // mapping of normalized device depths to window depths
+  pub depth_range: (f64, f64),
+  // stencil test, disabled if None
+  pub stencil: Option<StencilTest>,
+  // blending overriding the one of the render command, if any
+  pub blending: Option<Blending>,
+  // constant color used by the constant blending factors
+  pub blend_color: [f32; 4],
+  // blending of given draw buffers, overriding the one of all the draw buffers; it requires
+  // ARB_draw_buffers_blend and is ignored without it
+  pub draw_buffer_blendings: Vec<(usize, Option<Blending>)>,
+  // viewport; the whole framebuffer if None
+  pub viewport: Option<Rect>,
+  // scissor rectangle; the scissor test is disabled if None
+  pub scissor: Option<Rect>,
+  pub polygon_mode: PolygonMode,
+  // depth offset of polygons rasterized with polygon_mode, if any
+  pub polygon_offset: Option<PolygonOffset>
+}
+
+impl Default for RenderState {
+  fn default() -> Self {
+    RenderState {
+      draw_buffers: None,
+      color_mask: ColorMask::default(),
+      attachment_color_masks: Vec::new(),
+      face_culling: FaceCulling::default(),
+      front_face: Winding::default(),
+      depth_comparison: DepthComparison::Less,
+      depth_write: true,
+      depth_clamp: false,
+      depth_range: (0., 1.),
+      stencil: None,
+      blending: None,
+      blend_color: [0., 0., 0., 0.],
+      draw_buffer_blendings: Vec::new(),
+      viewport: None,
+      scissor: None,
+      polygon_mode: PolygonMode::default(),
+      polygon_offset: None
+    }
+  }
+}
+
+// Properties of the framebuffer targeted by the running pipeline.
+#[derive(Clone, Copy)]
+struct FramebufferInfo {
+  default: bool,
+  color_nb: usize,
+  has_depth: bool,
+  has_stencil: bool,
+  // size of the part of the framebuffer rendered into
+  viewport_size: (u32, u32)
+}
+
+thread_local!(static FRAMEBUFFER_INFO: Cell<FramebufferInfo> = Cell::new(FramebufferInfo {
+  default: true,
+  color_nb: 1,
+  has_depth: true,
+  has_stencil: true,
+  viewport_size: (0, 0)
+}));
+
+// Render state set by the update closure of the running shading or render command.
+thread_local!(static PENDING_RENDER_STATE: RefCell<Option<RenderState>> = RefCell::new(None));
+
+// Whether the update closure of a shading or render command is running.
+thread_local!(static UPDATING_COMMAND: Cell<bool> = Cell::new(false));
+
+// Reasons why a render state cannot be set on the framebuffer of the running pipeline.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum RenderStateError {
+  // a draw buffer, a color mask or a draw buffer blending refers to a missing color attachment
+  MissingColorAttachment(usize),
+  // the stencil test is enabled on a framebuffer without stencil attachment
+  MissingStencilAttachment,
+  // no update closure of a shading or render command is running
+  NoRunningCommand
+}
+
+// Set the render state of the render command – or of all the render commands of the shading
+// command – which update closure is running.
+//
+// The state is checked against the framebuffer of the running pipeline; if it doesn’t apply, it’s
+// not set and the command keeps the state it would have had otherwise. Calls made anywhere else –
+// e.g. in the update closure of a tessellation – are rejected.
+pub fn set_render_state(state: RenderState) -> Result<(), RenderStateError> {
+  if !UPDATING_COMMAND.with(|updating| updating.get()) {
+    return Err(RenderStateError::NoRunningCommand);
+  }
+
+  check_render_state(&state, FRAMEBUFFER_INFO.with(|info| info.get()))?;
+
+  PENDING_RENDER_STATE.with(|pending| *pending.borrow_mut() = Some(state));
+
+  Ok(())
+}
+
+fn check_render_state(state: &RenderState, info: FramebufferInfo) -> Result<(), RenderStateError> {
+  // the default framebuffer has a single color buffer
+  let color_nb = if info.default { 1 } else { info.color_nb };
+  let draw_buffers = state.draw_buffers.iter().flat_map(|buffers| buffers.iter().cloned());
+  let masked = state.attachment_color_masks.iter().map(|&(i, _)| i);
+
+  if let Some(i) = draw_buffers.chain(masked).find(|&i| i >= color_nb) {
+    return Err(RenderStateError::MissingColorAttachment(i));
+  }
+
+  // per draw buffer blendings are also bound by the number of draw buffers
+  if !state.draw_buffer_blendings.is_empty() {
+    let mut max_draw_buffers: GLint = 0;
+
+    unsafe { gl::GetIntegerv(gl::MAX_DRAW_BUFFERS, &mut max_draw_buffers) };
+
+    let limit = color_nb.min(max_draw_buffers as usize);
+
+    if let Some(&(i, _)) = state.draw_buffer_blendings.iter().find(|&&(i, _)| i >= limit) {
+      return Err(RenderStateError::MissingColorAttachment(i));
+    }
+  }
+
+  if state.stencil.is_some() && !info.has_stencil {
+    return Err(RenderStateError::MissingStencilAttachment);
+  }
+
+  Ok(())
+}
+
+fn take_render_state() -> Option<RenderState> {
+  PENDING_RENDER_STATE.with(|pending| pending.borrow_mut().take())
+}
+
+// Run the update closure of a shading or render command, which may set a render state.
+fn update_command<F>(update: F) where F: FnOnce() {
+  UPDATING_COMMAND.with(|updating| updating.set(true));
+  update();
+  UPDATING_COMMAND.with(|updating| updating.set(false));
+}
+
+// Options of the next pipeline to run, set by `run_pipeline_with`.
+thread_local!(static PIPELINE_OPTIONS: RefCell<Option<PipelineOptions>> = RefCell::new(None));
+
+// Reasons why a pipeline cannot run with `run_pipeline_with`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PipelineError {
+  // a render command depth-tests a framebuffer without depth attachment
+  MissingDepthAttachment,
+  // the clear policy doesn’t apply to the framebuffer
+  Clear(ClearError)
+}
+
+// Run a pipeline with additional options.
+//
+// The pipeline is checked against its framebuffer first, and doesn’t run if it doesn’t apply.
+pub fn run_pipeline_with<L, D, CS, DS>(pipeline: &Pipeline<L, D, CS, DS>, options: &PipelineOptions) -> Result<(), PipelineError>
+    where L: Layerable,
+          D: Dimensionable,
+          D::Size: Copy,
+          CS: ColorSlot<GL33, L, D>,
+          DS: DepthSlot<GL33, L, D> {
+  check_pipeline(pipeline)?;
+  options.clear.check(&pipeline.framebuffer.repr).map_err(PipelineError::Clear)?;
+
+  PIPELINE_OPTIONS.with(|next| *next.borrow_mut() = Some(options.clone()));
+
+  pipeline.run();
+
+  Ok(())
+}
+
+fn check_pipeline<L, D, CS, DS>(pipeline: &Pipeline<L, D, CS, DS>) -> Result<(), PipelineError>
+    where L: Layerable,
+          D: Dimensionable,
+          D::Size: Copy,
+          CS: ColorSlot<GL33, L, D>,
+          DS: DepthSlot<GL33, L, D> {
+  let depth_tested = pipeline.shading_commands.iter().any(|shading_cmd| {
+    shading_cmd.next.render_commands.iter().any(|render_cmd| render_cmd.next.depth_test)
+  });
+
+  if depth_tested && !pipeline.framebuffer.repr.has_depth() {
+    return Err(PipelineError::MissingDepthAttachment);
+  }
+
+  Ok(())
+}
+
 impl HasPipeline for GL33 {
   fn run_pipeline<L, D, CS, DS>(cmd: &Pipeline<L, D, CS, DS>)
     where L: Layerable,
@@ -21,52 +557,155 @@ impl HasPipeline for GL33 {
           CS: ColorSlot<Self, L, D>,
           DS: DepthSlot<Self, L, D> {
     let clear_color = cmd.clear_color;
+    let options = PIPELINE_OPTIONS.with(|next| next.borrow_mut().take()).unwrap_or_default();
 
-    unsafe {
-      gl::BindFramebuffer(gl::FRAMEBUFFER, cmd.framebuffer.repr.handle);
-      gl::Viewport(0, 0, cmd.framebuffer.repr.w as GLint, cmd.framebuffer.repr.h as GLint);
-      gl::ClearColor(clear_color[0], clear_color[1], clear_color[2], clear_color[3]);
-      gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
-
-      // traverse the texture set and bind required textures
-      for (unit, tex) in cmd.texture_set.iter().enumerate() {
-        gl::ActiveTexture(gl::TEXTURE0 + unit as GLenum);
-        gl::BindTexture(tex.repr.target, tex.repr.handle);
-      }
+    // a state left over by an earlier pipeline doesn’t apply to this one
+    take_render_state();
 
-      // traverse the buffer set and bind required buffers
-      for (index, buf) in cmd.buffer_set.iter().enumerate() {
-        gl::BindBufferBase(gl::UNIFORM_BUFFER, index as GLuint, buf.repr.handle);
-      }
+    FRAMEBUFFER_INFO.with(|info| info.set(FramebufferInfo {
+      default: cmd.framebuffer.repr.handle == 0,
+      color_nb: cmd.framebuffer.repr.color_formats.len(),
+      has_depth: cmd.framebuffer.repr.has_depth(),
+      has_stencil: cmd.framebuffer.repr.has_stencil(),
+      viewport_size: cmd.framebuffer.repr.viewport_size()
+    }));
+
+    state::bind_framebuffer(gl::FRAMEBUFFER, cmd.framebuffer.repr.handle);
+
+    // start from the defaults, which also cover the whole framebuffer and don’t mask clears out
+    set_render_state_defaults();
+
+    clear(&cmd.framebuffer.repr, &options.clear, clear_color);
+
+    // traverse the texture set and bind required textures
+    for (unit, tex) in cmd.texture_set.iter().enumerate() {
+      state::active_texture(gl::TEXTURE0 + unit as GLenum);
+      state::bind_texture(tex.repr.target, tex.repr.handle);
+    }
+
+    // traverse the buffer set and bind required buffers
+    for (index, buf) in cmd.buffer_set.iter().enumerate() {
+      state::bind_uniform_buffer(index as GLuint, buf.repr.handle);
     }
 
     for piped_shading_cmd in &cmd.shading_commands {
       Self::run_shading_command(piped_shading_cmd);
     }
+
+    // restore the defaults so that they don’t leak into foreign code or later pipelines
+    set_render_state_defaults();
+
+    if let Some(ref mask) = options.invalidate {
+      cmd.framebuffer.repr.invalidate(mask);
+    }
   }
 
   fn run_shading_command<'a>(piped: &Pipe<'a, ShadingCommand>) {
     let update_program = &piped.update_program;
     let shading_cmd = &piped.next;
 
-    unsafe { gl::UseProgram(shading_cmd.program.0.id) };
+    state::use_program(shading_cmd.program.0.id);
 
-    update_program(&shading_cmd.program);
+    update_command(|| update_program(&shading_cmd.program));
+
+    let shading_state = take_render_state();
 
     for piped_render_cmd in &shading_cmd.render_commands {
-      run_render_command(&shading_cmd.program, piped_render_cmd);
+      run_render_command(&shading_cmd.program, piped_render_cmd, shading_state.as_ref());
     }
   }
 }
 
-fn run_render_command<'a>(program: &Program, piped: &Pipe<'a, RenderCommand<'a>>) {
+// Clear the bound framebuffer. Color attachments are cleared with glClearBuffer, so that integer
+// attachments get integer values. Clear values that don’t apply, already rejected by
+// `run_pipeline_with`, are skipped.
+fn clear(framebuffer: &GLFramebuffer, policy: &ClearPolicy, clear_color: [f32; 4]) {
+  let color_nb = if framebuffer.handle == 0 { 1 } else { framebuffer.color_formats.len() };
+  let values: Vec<_> = match policy.color {
+    ColorClear::Keep => Vec::new(),
+    ColorClear::ClearColor => (0..color_nb).map(|i| color_format(framebuffer, i).map(|iformat| convert_clear_color(clear_color, iformat))).collect(),
+    ColorClear::PerAttachment(ref values) => values.clone()
+  };
+
+  for (i, value) in values.into_iter().enumerate() {
+    let value = match value {
+      Some(value) => value,
+      None => continue
+    };
+
+    match color_format(framebuffer, i) {
+      Some(iformat) if is_clear_value_compatible(&value, iformat) => (),
+      _ => continue
+    }
+
+    unsafe {
+      match value {
+        ClearValue::Float(ref v) => gl::ClearBufferfv(gl::COLOR, i as GLint, v.as_ptr()),
+        ClearValue::Int(ref v) => gl::ClearBufferiv(gl::COLOR, i as GLint, v.as_ptr()),
+        ClearValue::UInt(ref v) => gl::ClearBufferuiv(gl::COLOR, i as GLint, v.as_ptr())
+      }
+    }
+  }
+
+  let mut bits = 0;
+
+  unsafe {
+    if let Some(depth) = policy.depth {
+      gl::ClearDepth(depth as GLdouble);
+      bits |= gl::DEPTH_BUFFER_BIT;
+    }
+
+    if let Some(stencil) = policy.stencil {
+      gl::ClearStencil(stencil);
+      bits |= gl::STENCIL_BUFFER_BIT;
+    }
+
+    if bits != 0 {
+      gl::Clear(bits);
+    }
+  }
+}
+
+// Internal format of a color attachment; the default framebuffer has a single floating color buffer.
+fn color_format(framebuffer: &GLFramebuffer, i: usize) -> Option<GLenum> {
+  if framebuffer.handle == 0 {
+    if i == 0 { Some(gl::RGBA32F) } else { None }
+  } else {
+    framebuffer.color_formats.get(i).cloned()
+  }
+}
+
+// Convert the clear color of a pipeline to the kind of values of a color attachment.
+fn convert_clear_color(color: [f32; 4], iformat: GLenum) -> ClearValue {
+  if is_signed_integer_format(iformat) {
+    ClearValue::Int([color[0] as i32, color[1] as i32, color[2] as i32, color[3] as i32])
+  } else if is_integer_format(iformat) {
+    ClearValue::UInt([color[0] as u32, color[1] as u32, color[2] as u32, color[3] as u32])
+  } else {
+    ClearValue::Float(color)
+  }
+}
+
+fn is_clear_value_compatible(value: &ClearValue, iformat: GLenum) -> bool {
+  match *value {
+    ClearValue::Float(_) => !is_integer_format(iformat),
+    ClearValue::Int(_) => is_signed_integer_format(iformat),
+    ClearValue::UInt(_) => is_integer_format(iformat) && !is_signed_integer_format(iformat)
+  }
+}
+
+fn run_render_command<'a>(program: &Program, piped: &Pipe<'a, RenderCommand<'a>>, shading_state: Option<&RenderState>) {
   let update_program = &piped.update_program;
   let render_cmd = &piped.next;
 
-  update_program(program);
+  update_command(|| update_program(program));
+
+  let state = take_render_state().or_else(|| shading_state.cloned()).unwrap_or_default();
+
+  let blending = state.blending.or_else(|| render_cmd.blending.map(Blending::from));
 
-  set_blending(render_cmd.blending);
   set_depth_test(render_cmd.depth_test);
+  apply_render_state(&state, blending);
 
   for piped_tess in &render_cmd.tessellations {
     let tess_update_program = &piped_tess.update_program;
@@ -76,23 +715,47 @@ fn run_render_command<'a>(program: &Program, piped: &Pipe<'a, RenderCommand<'a>>
 
     (tess.repr.render)(render_cmd.rasterization_size, render_cmd.instances);
   }
+
+  // nothing set past the update closure of the render command applies to the next one
+  take_render_state();
 }
 
-fn set_blending(blending: Option<(blending::Equation, blending::Factor, blending::Factor)>) {
+fn set_blending(blending: Option<Blending>, color: [f32; 4], draw_buffer_blendings: &[(usize, Option<Blending>)]) {
   match blending {
-    Some((equation, src_factor, dest_factor)) => {
-      unsafe {
-        gl::Enable(gl::BLEND);
-        gl::BlendEquation(from_blending_equation(equation));
-        gl::BlendFunc(from_blending_factor(src_factor), from_blending_factor(dest_factor));
-      }
+    Some(blending) => {
+      let (equations, funcs) = from_blending(blending);
+
+      state::set_capability(gl::BLEND, true);
+      state::set_blend_equation(equations.0, equations.1);
+      state::set_blend_func(funcs.0, funcs.1, funcs.2, funcs.3);
     },
     None => {
-      unsafe { gl::Disable(gl::BLEND) };
+      state::set_capability(gl::BLEND, false);
+    }
+  }
+
+  state::set_blend_color(color);
+
+  if !draw_buffer_blendings.is_empty() && has_draw_buffers_blend() {
+    for &(i, blending) in draw_buffer_blendings {
+      state::set_draw_buffer_blending(i as GLuint, blending.map(from_blending));
     }
   }
 }
 
+fn has_draw_buffers_blend() -> bool {
+  has_extension("GL_ARB_draw_buffers_blend") && gl::BlendFuncSeparatei::is_loaded() && gl::BlendEquationSeparatei::is_loaded()
+}
+
+// Get the RGB and alpha equations and the RGB and alpha source and destination factors.
+fn from_blending(blending: Blending) -> ((GLenum, GLenum), (GLenum, GLenum, GLenum, GLenum)) {
+  let equations = (from_blending_equation(blending.rgb_equation), from_blending_equation(blending.alpha_equation));
+  let funcs = (from_blending_factor(blending.rgb_src), from_blending_factor(blending.rgb_dst),
+               from_blending_factor(blending.alpha_src), from_blending_factor(blending.alpha_dst));
+
+  (equations, funcs)
+}
+
 fn from_blending_equation(equation: blending::Equation) -> GLenum {
   match equation {
     blending::Equation::Additive => gl::FUNC_ADD,
@@ -103,28 +766,181 @@ fn from_blending_equation(equation: blending::Equation) -> GLenum {
   }
 }
 
-fn from_blending_factor(factor: blending::Factor) -> GLenum {
+fn from_blending_factor(factor: BlendFactor) -> GLenum {
   match factor {
-    blending::Factor::One => gl::ONE,
-    blending::Factor::Zero => gl::ZERO,
-    blending::Factor::SrcColor => gl::SRC_COLOR,
-    blending::Factor::SrcColorComplement => gl::ONE_MINUS_SRC_COLOR,
-    blending::Factor::DestColor => gl::DST_COLOR,
-    blending::Factor::DestColorComplement => gl::ONE_MINUS_DST_COLOR,
-    blending::Factor::SrcAlpha => gl::SRC_ALPHA,
-    blending::Factor::SrcAlphaComplement => gl::ONE_MINUS_SRC_ALPHA,
-    blending::Factor::DstAlpha => gl::DST_ALPHA,
-    blending::Factor::DstAlphaComplement => gl::ONE_MINUS_DST_ALPHA,
-    blending::Factor::SrcAlphaSaturate => gl::SRC_ALPHA_SATURATE
+    BlendFactor::One => gl::ONE,
+    BlendFactor::Zero => gl::ZERO,
+    BlendFactor::SrcColor => gl::SRC_COLOR,
+    BlendFactor::SrcColorComplement => gl::ONE_MINUS_SRC_COLOR,
+    BlendFactor::DestColor => gl::DST_COLOR,
+    BlendFactor::DestColorComplement => gl::ONE_MINUS_DST_COLOR,
+    BlendFactor::SrcAlpha => gl::SRC_ALPHA,
+    BlendFactor::SrcAlphaComplement => gl::ONE_MINUS_SRC_ALPHA,
+    BlendFactor::DstAlpha => gl::DST_ALPHA,
+    BlendFactor::DstAlphaComplement => gl::ONE_MINUS_DST_ALPHA,
+    BlendFactor::SrcAlphaSaturate => gl::SRC_ALPHA_SATURATE,
+    BlendFactor::ConstantColor => gl::CONSTANT_COLOR,
+    BlendFactor::ConstantColorComplement => gl::ONE_MINUS_CONSTANT_COLOR,
+    BlendFactor::ConstantAlpha => gl::CONSTANT_ALPHA,
+    BlendFactor::ConstantAlphaComplement => gl::ONE_MINUS_CONSTANT_ALPHA
   }
 }
 
+// Framebuffers without depth attachment are rejected by `run_pipeline_with`; pipelines run by
+// luminance get the depth test disabled instead, as OpenGL does without depth buffer.
 fn set_depth_test(test: bool) {
-  unsafe {
-    if test {
-      gl::Enable(gl::DEPTH_TEST);
-    } else {
-      gl::Disable(gl::DEPTH_TEST);
+  let has_depth = FRAMEBUFFER_INFO.with(|info| info.get().has_depth);
+
+  state::set_capability(gl::DEPTH_TEST, test && has_depth);
+}
+
+// Select the color attachments to draw to, or all of them with None.
+//
+// Fragment outputs keep their locations: the draw buffers of non-selected attachments are NONE.
+// The selection was checked by `set_render_state`.
+fn set_draw_buffers(selection: Option<&[usize]>) {
+  let info = FRAMEBUFFER_INFO.with(|info| info.get());
+
+  let is_selected = |i: usize| selection.map_or(true, |selection| selection.contains(&i));
+
+  if info.default {
+    state::set_draw_buffers(&[if is_selected(0) { gl::BACK } else { gl::NONE }]);
+  } else if info.color_nb == 0 {
+    state::set_draw_buffers(&[gl::NONE]);
+  } else {
+    let color_buffers: Vec<_> = (0..info.color_nb).map(|i| {
+      if is_selected(i) { gl::COLOR_ATTACHMENT0 + i as GLenum } else { gl::NONE }
+    }).collect();
+
+    state::set_draw_buffers(&color_buffers);
+  }
+}
+
+fn set_color_masks(mask: ColorMask, attachment_masks: &[(usize, ColorMask)]) {
+  state::set_color_mask([mask.r, mask.g, mask.b, mask.a]);
+
+  for &(i, mask) in attachment_masks {
+    state::set_color_mask_indexed(i as GLuint, [mask.r, mask.g, mask.b, mask.a]);
+  }
+}
+
+fn set_face_culling(face_culling: FaceCulling, front_face: Winding) {
+  match face_culling {
+    FaceCulling::None => state::set_capability(gl::CULL_FACE, false),
+    _ => {
+      state::set_capability(gl::CULL_FACE, true);
+      state::set_cull_face(from_face_culling(face_culling));
     }
   }
+
+  state::set_front_face(match front_face {
+    Winding::CW => gl::CW,
+    Winding::CCW => gl::CCW
+  });
+}
+
+fn from_face_culling(face_culling: FaceCulling) -> GLenum {
+  match face_culling {
+    FaceCulling::Front => gl::FRONT,
+    FaceCulling::Back | FaceCulling::None => gl::BACK,
+    FaceCulling::FrontAndBack => gl::FRONT_AND_BACK
+  }
+}
+
+// The presence of a stencil attachment was checked by `set_render_state`.
+fn set_stencil_test(test: Option<StencilTest>) {
+  state::set_capability(gl::STENCIL_TEST, test.is_some());
+
+  // the write masks also apply to clears, so they’re reset even when the test is disabled
+  let test = test.unwrap_or_default();
+
+  for &(face, ref stencil) in &[(gl::FRONT, test.front), (gl::BACK, test.back)] {
+    state::set_stencil_func(face, from_depth_comparison(stencil.comparison), stencil.reference, stencil.read_mask);
+    state::set_stencil_op(face, from_stencil_op(stencil.stencil_fail), from_stencil_op(stencil.depth_fail), from_stencil_op(stencil.depth_pass));
+    state::set_stencil_mask(face, stencil.write_mask);
+  }
+}
+
+fn from_stencil_op(op: StencilOp) -> GLenum {
+  match op {
+    StencilOp::Keep => gl::KEEP,
+    StencilOp::Zero => gl::ZERO,
+    StencilOp::Replace => gl::REPLACE,
+    StencilOp::Increment => gl::INCR,
+    StencilOp::IncrementWrap => gl::INCR_WRAP,
+    StencilOp::Decrement => gl::DECR,
+    StencilOp::DecrementWrap => gl::DECR_WRAP,
+    StencilOp::Invert => gl::INVERT
+  }
+}
+
+fn set_viewport_scissor(viewport: Option<Rect>, scissor: Option<Rect>) {
+  let viewport = viewport.unwrap_or_else(|| {
+    let (w, h) = FRAMEBUFFER_INFO.with(|info| info.get().viewport_size);
+    Rect::new(0, 0, w, h)
+  });
+
+  state::set_viewport(viewport.x as GLint, viewport.y as GLint, viewport.w as GLsizei, viewport.h as GLsizei);
+  state::set_capability(gl::SCISSOR_TEST, scissor.is_some());
+
+  if let Some(scissor) = scissor {
+    state::set_scissor(scissor.x as GLint, scissor.y as GLint, scissor.w as GLsizei, scissor.h as GLsizei);
+  }
+}
+
+fn set_polygon_mode(mode: PolygonMode, offset: Option<PolygonOffset>) {
+  let (gl_mode, offset_capability) = match mode {
+    PolygonMode::Fill => (gl::FILL, gl::POLYGON_OFFSET_FILL),
+    PolygonMode::Line => (gl::LINE, gl::POLYGON_OFFSET_LINE),
+    PolygonMode::Point => (gl::POINT, gl::POLYGON_OFFSET_POINT)
+  };
+
+  state::set_polygon_mode(gl_mode);
+
+  // only the offset of the mode in use is enabled
+  for &capability in &[gl::POLYGON_OFFSET_FILL, gl::POLYGON_OFFSET_LINE, gl::POLYGON_OFFSET_POINT] {
+    state::set_capability(capability, offset.is_some() && capability == offset_capability);
+  }
+
+  if let Some(offset) = offset {
+    state::set_polygon_offset(offset.factor, offset.units);
+  }
+}
+
+fn apply_render_state(state: &RenderState, blending: Option<Blending>) {
+  set_blending(blending, state.blend_color, &state.draw_buffer_blendings);
+  set_draw_buffers(state.draw_buffers.as_ref().map(|buffers| buffers.as_slice()));
+  set_color_masks(state.color_mask, &state.attachment_color_masks);
+  set_face_culling(state.face_culling, state.front_face);
+  state::set_depth_func(from_depth_comparison(state.depth_comparison));
+  state::set_depth_mask(state.depth_write);
+  state::set_capability(gl::DEPTH_CLAMP, state.depth_clamp);
+  state::set_depth_range(state.depth_range.0, state.depth_range.1);
+  set_stencil_test(state.stencil);
+  set_viewport_scissor(state.viewport, state.scissor);
+  set_polygon_mode(state.polygon_mode, state.polygon_offset);
+}
+
+// Restore the default render state.
+fn set_render_state_defaults() {
+  apply_render_state(&RenderState::default(), None);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn render_state_outside_command_is_rejected() {
+    assert_eq!(set_render_state(RenderState::default()), Err(RenderStateError::NoRunningCommand));
+    assert!(take_render_state().is_none());
+  }
+
+  #[test]
+  fn render_state_in_command_is_pending() {
+    update_command(|| assert_eq!(set_render_state(RenderState::default()), Ok(())));
+
+    assert!(take_render_state().is_some());
+    assert_eq!(set_render_state(RenderState::default()), Err(RenderStateError::NoRunningCommand));
+  }
 }
@@ -7,6 +7,7 @@ use std::collections::HashMap;
 use std::ffi::CString;
 use std::ptr::null_mut;
 
+use gl33::state;
 use gl33::token::GL33;
 
 pub type Program = program::Program<GL33>;
@@ -89,9 +90,9 @@ impl HasProgram for GL33 {
   }
 
   fn update_uniforms<F>(program: &Self::Program, f: F) where F: Fn() {
-    unsafe { gl::UseProgram(program.id) };
+    state::use_program(program.id);
     f();
-    unsafe { gl::UseProgram(0) };
+    state::use_program(0);
   }
 
   fn update1_i32(program: &Self::Program, u: SemIndex, x: i32) {
@@ -1,5 +1,6 @@
 use gl;
 use gl::types::*;
+use gl33::state;
 use gl33::token::GL33;
 use luminance::linear::*;
 use luminance::shader::uniform;
@@ -172,8 +173,8 @@ impl uniform::HasUniform for GL33 {
   fn update_textures(u: &Self::U, textures: &[&Self::ATexture]) {
     for (tex_unit, texture) in textures.iter().enumerate() {
       unsafe {
-        gl::ActiveTexture(gl::TEXTURE0 + tex_unit as GLenum);
-        gl::BindTexture(texture.target, texture.handle);
+        state::active_texture(gl::TEXTURE0 + tex_unit as GLenum);
+        state::bind_texture(texture.target, texture.handle);
         gl::Uniform1i(*u, tex_unit as GLint);
       }
     }
@@ -0,0 +1,481 @@
+use gl;
+use gl::types::*;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::mem;
+
+use gl33::extension::forget_extensions;
+
+// Numbers of OpenGL state calls that went through the state cache.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct StateStats {
+  // calls issued to OpenGL
+  pub issued: usize,
+  // redundant calls skipped
+  pub skipped: usize
+}
+
+// Cache of the OpenGL state set by the backend, used to skip redundant calls. None stands for an
+// unknown state.
+#[derive(Default)]
+struct GLState {
+  draw_framebuffer: Option<GLuint>,
+  read_framebuffer: Option<GLuint>,
+  // draw buffers of the bound draw framebuffer
+  draw_buffers: Option<Vec<GLenum>>,
+  program: Option<GLuint>,
+  active_texture: Option<GLenum>,
+  // bound textures per (unit, target)
+  textures: HashMap<(GLenum, GLenum), Option<GLuint>>,
+  // bound uniform buffers per binding index
+  uniform_buffers: HashMap<GLuint, Option<GLuint>>,
+  vertex_array: Option<GLuint>,
+  capabilities: HashMap<GLenum, Option<bool>>,
+  // RGB and alpha equations
+  blend_equation: Option<(GLenum, GLenum)>,
+  // RGB and alpha source and destination factors
+  blend_func: Option<(GLenum, GLenum, GLenum, GLenum)>,
+  blend_color: Option<[f32; 4]>,
+  color_mask: Option<[bool; 4]>,
+  cull_face: Option<GLenum>,
+  front_face: Option<GLenum>,
+  depth_func: Option<GLenum>,
+  depth_mask: Option<bool>,
+  depth_range: Option<(GLdouble, GLdouble)>,
+  // stencil state of front and back faces
+  stencil_funcs: [Option<(GLenum, GLint, GLuint)>; 2],
+  stencil_ops: [Option<(GLenum, GLenum, GLenum)>; 2],
+  stencil_masks: [Option<GLuint>; 2],
+  viewport: Option<(GLint, GLint, GLsizei, GLsizei)>,
+  scissor: Option<(GLint, GLint, GLsizei, GLsizei)>,
+  polygon_mode: Option<GLenum>,
+  // factor and units
+  polygon_offset: Option<(GLfloat, GLfloat)>,
+  primitive_restart_index: Option<GLuint>,
+  stats: StateStats
+}
+
+// Identifier of an OpenGL context, chosen by the application; 0 is the context used if none is
+// ever made current.
+pub type ContextId = usize;
+
+// State of the context current on this thread, as a context is current on a single thread.
+thread_local!(static STATE: RefCell<GLState> = RefCell::new(GLState::default()));
+
+// Current context and cached states of the other contexts used on this thread.
+thread_local!(static CONTEXTS: RefCell<(ContextId, HashMap<ContextId, GLState>)> = RefCell::new((0, HashMap::new())));
+
+// Set a cached value, calling `set` only if it’s different from the cached one.
+fn cached<T, G, F>(get: G, value: T, set: F) where T: PartialEq, G: FnOnce(&mut GLState) -> &mut Option<T>, F: FnOnce() {
+  STATE.with(|state| {
+    let mut state = state.borrow_mut();
+
+    let changed = {
+      let cached = get(&mut state);
+
+      if cached.as_ref() == Some(&value) {
+        false
+      } else {
+        *cached = Some(value);
+        true
+      }
+    };
+
+    if changed {
+      state.stats.issued += 1;
+      set();
+    } else {
+      state.stats.skipped += 1;
+    }
+  })
+}
+
+fn entry<K, V>(map: &mut HashMap<K, Option<V>>, key: K) -> &mut Option<V> where K: Eq + Hash {
+  map.entry(key).or_insert(None)
+}
+
+// Tell the backend that another OpenGL context is now current on this thread.
+//
+// Each context has its own cached state, so this must be called on every context switch when
+// several contexts are used on the same thread. Statistics are kept across switches.
+pub fn make_current(context: ContextId) {
+  CONTEXTS.with(|contexts| {
+    let mut contexts = contexts.borrow_mut();
+
+    if contexts.0 == context {
+      return;
+    }
+
+    let next = contexts.1.remove(&context).unwrap_or_default();
+
+    let previous = STATE.with(|state| {
+      let mut state = state.borrow_mut();
+      let stats = state.stats;
+      let previous = mem::replace(&mut *state, next);
+
+      state.stats = stats;
+      previous
+    });
+
+    let current = contexts.0;
+    contexts.1.insert(current, previous);
+    contexts.0 = context;
+  })
+}
+
+// Context current on this thread, as last set with `make_current`.
+pub fn current_context() -> ContextId {
+  CONTEXTS.with(|contexts| contexts.borrow().0)
+}
+
+// Forget everything about the OpenGL state of the current context, including its extensions.
+//
+// This must be called whenever code outside of the backend has changed the OpenGL state, and when
+// a context is destroyed and another one is made current under the same identifier.
+pub fn invalidate() {
+  STATE.with(|state| {
+    let mut state = state.borrow_mut();
+    let stats = state.stats;
+
+    *state = GLState::default();
+    state.stats = stats;
+  });
+
+  forget_extensions();
+}
+
+pub fn stats() -> StateStats {
+  STATE.with(|state| state.borrow().stats)
+}
+
+pub fn reset_stats() {
+  STATE.with(|state| state.borrow_mut().stats = StateStats::default())
+}
+
+// Bind a framebuffer to FRAMEBUFFER, READ_FRAMEBUFFER or DRAW_FRAMEBUFFER.
+pub fn bind_framebuffer(target: GLenum, framebuffer: GLuint) {
+  let (draw, read) = match target {
+    gl::DRAW_FRAMEBUFFER => (true, false),
+    gl::READ_FRAMEBUFFER => (false, true),
+    _ => (true, true)
+  };
+
+  STATE.with(|state| {
+    let mut state = state.borrow_mut();
+
+    let draw_changed = draw && state.draw_framebuffer != Some(framebuffer);
+    let read_changed = read && state.read_framebuffer != Some(framebuffer);
+
+    if draw_changed || read_changed {
+      state.stats.issued += 1;
+      unsafe { gl::BindFramebuffer(target, framebuffer) };
+    } else {
+      state.stats.skipped += 1;
+    }
+
+    if draw_changed {
+      state.draw_framebuffer = Some(framebuffer);
+      state.draw_buffers = None;
+    }
+
+    if read_changed {
+      state.read_framebuffer = Some(framebuffer);
+    }
+  })
+}
+
+// Set the draw buffers of the bound draw framebuffer.
+//
+// A single buffer is set with glDrawBuffer, as glDrawBuffers doesn’t accept BACK.
+pub fn set_draw_buffers(buffers: &[GLenum]) {
+  cached(|state| &mut state.draw_buffers, buffers.to_vec(), || unsafe {
+    if buffers.len() == 1 {
+      gl::DrawBuffer(buffers[0]);
+    } else {
+      gl::DrawBuffers(buffers.len() as GLsizei, buffers.as_ptr());
+    }
+  })
+}
+
+pub fn use_program(program: GLuint) {
+  cached(|state| &mut state.program, program, || unsafe { gl::UseProgram(program) })
+}
+
+// Select the active texture unit (TEXTURE0 + unit).
+pub fn active_texture(unit: GLenum) {
+  cached(|state| &mut state.active_texture, unit, || unsafe { gl::ActiveTexture(unit) })
+}
+
+// Bind a texture to the active texture unit.
+pub fn bind_texture(target: GLenum, texture: GLuint) {
+  match STATE.with(|state| state.borrow().active_texture) {
+    Some(unit) => cached(|state| entry(&mut state.textures, (unit, target)), texture, || unsafe { gl::BindTexture(target, texture) }),
+    None => {
+      // the unit is unknown, so are the bindings
+      STATE.with(|state| {
+        let mut state = state.borrow_mut();
+
+        state.textures.clear();
+        state.stats.issued += 1;
+      });
+
+      unsafe { gl::BindTexture(target, texture) };
+    }
+  }
+}
+
+pub fn bind_uniform_buffer(index: GLuint, buffer: GLuint) {
+  cached(|state| entry(&mut state.uniform_buffers, index), buffer, || unsafe { gl::BindBufferBase(gl::UNIFORM_BUFFER, index, buffer) })
+}
+
+pub fn bind_vertex_array(vertex_array: GLuint) {
+  cached(|state| &mut state.vertex_array, vertex_array, || unsafe { gl::BindVertexArray(vertex_array) })
+}
+
+// Enable or disable a capability.
+pub fn set_capability(capability: GLenum, enabled: bool) {
+  cached(|state| entry(&mut state.capabilities, capability), enabled, || unsafe {
+    if enabled {
+      gl::Enable(capability);
+    } else {
+      gl::Disable(capability);
+    }
+  })
+}
+
+pub fn set_blend_equation(rgb: GLenum, alpha: GLenum) {
+  cached(|state| &mut state.blend_equation, (rgb, alpha), || unsafe { gl::BlendEquationSeparate(rgb, alpha) })
+}
+
+pub fn set_blend_func(src_rgb: GLenum, dst_rgb: GLenum, src_alpha: GLenum, dst_alpha: GLenum) {
+  cached(|state| &mut state.blend_func, (src_rgb, dst_rgb, src_alpha, dst_alpha), || unsafe {
+    gl::BlendFuncSeparate(src_rgb, dst_rgb, src_alpha, dst_alpha)
+  })
+}
+
+pub fn set_blend_color(color: [f32; 4]) {
+  cached(|state| &mut state.blend_color, color, || unsafe { gl::BlendColor(color[0], color[1], color[2], color[3]) })
+}
+
+// Enable blending of a single draw buffer with the given RGB and alpha equations and factors, or
+// disable it with None.
+pub fn set_draw_buffer_blending(index: GLuint, blending: Option<((GLenum, GLenum), (GLenum, GLenum, GLenum, GLenum))>) {
+  STATE.with(|state| {
+    let mut state = state.borrow_mut();
+
+    // the draw buffers no longer share the same blending
+    *entry(&mut state.capabilities, gl::BLEND) = None;
+    state.blend_equation = None;
+    state.blend_func = None;
+    state.stats.issued += 1;
+  });
+
+  unsafe {
+    match blending {
+      Some(((rgb, alpha), (src_rgb, dst_rgb, src_alpha, dst_alpha))) => {
+        gl::Enablei(gl::BLEND, index);
+        gl::BlendEquationSeparatei(index, rgb, alpha);
+        gl::BlendFuncSeparatei(index, src_rgb, dst_rgb, src_alpha, dst_alpha);
+      },
+      None => gl::Disablei(gl::BLEND, index)
+    }
+  }
+}
+
+// Set the color write mask of all the draw buffers.
+pub fn set_color_mask(mask: [bool; 4]) {
+  cached(|state| &mut state.color_mask, mask, || unsafe {
+    gl::ColorMask(mask[0] as GLboolean, mask[1] as GLboolean, mask[2] as GLboolean, mask[3] as GLboolean)
+  })
+}
+
+// Set the color write mask of a single draw buffer.
+pub fn set_color_mask_indexed(index: GLuint, mask: [bool; 4]) {
+  STATE.with(|state| {
+    let mut state = state.borrow_mut();
+
+    // the draw buffers no longer share the same mask
+    state.color_mask = None;
+    state.stats.issued += 1;
+  });
+
+  unsafe { gl::ColorMaski(index, mask[0] as GLboolean, mask[1] as GLboolean, mask[2] as GLboolean, mask[3] as GLboolean) };
+}
+
+pub fn set_cull_face(mode: GLenum) {
+  cached(|state| &mut state.cull_face, mode, || unsafe { gl::CullFace(mode) })
+}
+
+pub fn set_front_face(mode: GLenum) {
+  cached(|state| &mut state.front_face, mode, || unsafe { gl::FrontFace(mode) })
+}
+
+pub fn set_depth_func(func: GLenum) {
+  cached(|state| &mut state.depth_func, func, || unsafe { gl::DepthFunc(func) })
+}
+
+pub fn set_depth_mask(write: bool) {
+  cached(|state| &mut state.depth_mask, write, || unsafe { gl::DepthMask(write as GLboolean) })
+}
+
+pub fn set_depth_range(near: GLdouble, far: GLdouble) {
+  cached(|state| &mut state.depth_range, (near, far), || unsafe { gl::DepthRange(near, far) })
+}
+
+fn stencil_face_index(face: GLenum) -> usize {
+  if face == gl::BACK { 1 } else { 0 }
+}
+
+// Set the stencil function of FRONT or BACK faces.
+pub fn set_stencil_func(face: GLenum, func: GLenum, reference: GLint, mask: GLuint) {
+  cached(|state| &mut state.stencil_funcs[stencil_face_index(face)], (func, reference, mask), || unsafe {
+    gl::StencilFuncSeparate(face, func, reference, mask)
+  })
+}
+
+// Set the stencil operations of FRONT or BACK faces.
+pub fn set_stencil_op(face: GLenum, sfail: GLenum, dpfail: GLenum, dppass: GLenum) {
+  cached(|state| &mut state.stencil_ops[stencil_face_index(face)], (sfail, dpfail, dppass), || unsafe {
+    gl::StencilOpSeparate(face, sfail, dpfail, dppass)
+  })
+}
+
+// Set the stencil write mask of FRONT or BACK faces.
+pub fn set_stencil_mask(face: GLenum, mask: GLuint) {
+  cached(|state| &mut state.stencil_masks[stencil_face_index(face)], mask, || unsafe { gl::StencilMaskSeparate(face, mask) })
+}
+
+pub fn set_viewport(x: GLint, y: GLint, w: GLsizei, h: GLsizei) {
+  cached(|state| &mut state.viewport, (x, y, w, h), || unsafe { gl::Viewport(x, y, w, h) })
+}
+
+pub fn set_scissor(x: GLint, y: GLint, w: GLsizei, h: GLsizei) {
+  cached(|state| &mut state.scissor, (x, y, w, h), || unsafe { gl::Scissor(x, y, w, h) })
+}
+
+// Set the polygon mode of both front and back faces.
+pub fn set_polygon_mode(mode: GLenum) {
+  cached(|state| &mut state.polygon_mode, mode, || unsafe { gl::PolygonMode(gl::FRONT_AND_BACK, mode) })
+}
+
+pub fn set_polygon_offset(factor: GLfloat, units: GLfloat) {
+  cached(|state| &mut state.polygon_offset, (factor, units), || unsafe { gl::PolygonOffset(factor, units) })
+}
+
+pub fn set_primitive_restart_index(index: GLuint) {
+  cached(|state| &mut state.primitive_restart_index, index, || unsafe { gl::PrimitiveRestartIndex(index) })
+}
+
+// Forget a deleted framebuffer; OpenGL falls back to the default framebuffer if it was bound.
+pub fn forget_framebuffer(framebuffer: GLuint) {
+  STATE.with(|state| {
+    let mut state = state.borrow_mut();
+
+    if state.draw_framebuffer == Some(framebuffer) {
+      state.draw_framebuffer = Some(0);
+      state.draw_buffers = None;
+    }
+
+    if state.read_framebuffer == Some(framebuffer) {
+      state.read_framebuffer = Some(0);
+    }
+  })
+}
+
+// Forget a deleted texture; OpenGL unbinds it from all units.
+pub fn forget_texture(texture: GLuint) {
+  STATE.with(|state| {
+    for bound in state.borrow_mut().textures.values_mut().filter(|bound| **bound == Some(texture)) {
+      *bound = Some(0);
+    }
+  })
+}
+
+// Forget a deleted buffer; OpenGL unbinds it from all binding points.
+pub fn forget_buffer(buffer: GLuint) {
+  STATE.with(|state| {
+    for bound in state.borrow_mut().uniform_buffers.values_mut().filter(|bound| **bound == Some(buffer)) {
+      *bound = Some(0);
+    }
+  })
+}
+
+// Forget a deleted vertex array; OpenGL binds 0 instead if it was bound.
+pub fn forget_vertex_array(vertex_array: GLuint) {
+  STATE.with(|state| {
+    let mut state = state.borrow_mut();
+
+    if state.vertex_array == Some(vertex_array) {
+      state.vertex_array = Some(0);
+    }
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use gl;
+  use gl::types::*;
+  use std::os::raw::c_void;
+
+  use super::*;
+
+  extern "system" fn bind_vertex_array_stub(_: GLuint) {}
+  extern "system" fn capability_stub(_: GLenum) {}
+
+  // Load no-op OpenGL functions, so that the cache can be exercised without a context.
+  fn load_stubs() {
+    gl::BindVertexArray::load_with(|_| bind_vertex_array_stub as *const c_void);
+    gl::Enable::load_with(|_| capability_stub as *const c_void);
+    gl::Disable::load_with(|_| capability_stub as *const c_void);
+  }
+
+  #[test]
+  fn redundant_vertex_array_binding_is_skipped() {
+    load_stubs();
+    reset_stats();
+
+    bind_vertex_array(3);
+    bind_vertex_array(3);
+
+    assert_eq!(stats(), StateStats { issued: 1, skipped: 1 });
+  }
+
+  #[test]
+  fn redundant_capability_is_skipped() {
+    load_stubs();
+    reset_stats();
+
+    set_capability(gl::BLEND, true);
+    set_capability(gl::BLEND, true);
+
+    assert_eq!(stats(), StateStats { issued: 1, skipped: 1 });
+  }
+
+  #[test]
+  fn invalidate_forgets_cached_state() {
+    load_stubs();
+    reset_stats();
+
+    bind_vertex_array(3);
+    invalidate();
+    bind_vertex_array(3);
+
+    assert_eq!(stats(), StateStats { issued: 2, skipped: 0 });
+  }
+
+  #[test]
+  fn contexts_have_separate_states() {
+    load_stubs();
+    reset_stats();
+
+    bind_vertex_array(3);
+    make_current(1);
+    bind_vertex_array(3);
+    make_current(0);
+    bind_vertex_array(3);
+
+    assert_eq!(current_context(), 0);
+    assert_eq!(stats(), StateStats { issued: 2, skipped: 1 });
+  }
+}
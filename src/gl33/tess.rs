@@ -2,10 +2,13 @@ use gl;
 use gl::types::*;
 use luminance::tess::{self, HasTess, Mode};
 use luminance::vertex::{Dim, Type, Vertex, VertexComponentFormat, VertexFormat};
+use std::marker::PhantomData;
 use std::mem;
+use std::ops::Deref;
 use std::ptr;
 
 use gl33::buffer::{Buffer, GLBuffer};
+use gl33::state;
 use gl33::token::GL33;
 
 pub type Tess = tess::Tess<GL33>;
@@ -17,105 +20,185 @@ pub struct GLTess {
   vbo: Option<GLBuffer>,
   ibo: Option<GLBuffer>,
   vertex_format: VertexFormat,
-  vert_nb: usize
+  vert_nb: usize,
+  mode: Mode,
+  draw: Draw,
+  owned: bool // whether the vertex array and buffers are freed along with this tessellation
 }
 
-impl HasTess for GL33 {
-  type Tess = GLTess;
-
-  fn new_tess<T>(mode: Mode, vertices: &[T], indices: Option<&[u32]>) -> Self::Tess where T: Vertex {
-    let mut vao: GLuint = 0;
-    let vert_nb = vertices.len();
+impl GLTess {
+  // Tessellation drawing a range of the vertices or indices of this one, relative to the range this
+  // one draws. It shares the vertex array and buffers of this tessellation and borrows it.
+  //
+  // In debug builds, the indices of the range are read back to check that no vertex past the end
+  // of the vertex buffer is fetched.
+  pub fn slice<'a>(&'a self, range: TessRange) -> Result<TessSlice<'a>, TessRangeError> {
+    let draw = match (range, self.draw) {
+      (TessRange::Vertices { start, count }, Draw::Arrays { start: parent_start, count: parent_count }) => {
+        check_range(start, count, parent_count)?;
+        Draw::Arrays { start: parent_start + start, count: count }
+      },
+
+      (TessRange::Indices { offset, count, base_vertex }, Draw::Elements { index_type, offset: parent_offset, count: parent_count, base_vertex: parent_base_vertex, primitive_restart }) => {
+        check_range(offset, count, parent_count)?;
+
+        let base_vertex = match parent_base_vertex.checked_add(base_vertex) {
+          Some(base_vertex) if base_vertex >= 0 && (base_vertex as usize) < self.vert_nb.max(1) => base_vertex,
+          _ => return Err(TessRangeError::BaseVertexOutOfRange(base_vertex, self.vert_nb))
+        };
+
+        let offset = parent_offset + offset;
+
+        if cfg!(debug_assertions) {
+          if let Some(ref ibo) = self.ibo {
+            if let Some(index) = max_index(ibo.handle, index_type, offset, count, primitive_restart) {
+              let vertex = index as usize + base_vertex as usize;
+
+              if vertex >= self.vert_nb {
+                return Err(TessRangeError::VertexOutOfRange(vertex, self.vert_nb));
+              }
+            }
+          }
+        }
 
-    unsafe {
-      gl::GenVertexArrays(1, &mut vao);
+        Draw::Elements {
+          index_type: index_type,
+          offset: offset,
+          count: count,
+          base_vertex: base_vertex,
+          primitive_restart: primitive_restart
+        }
+      },
+
+      (TessRange::Vertices { .. }, Draw::Elements { .. }) => return Err(TessRangeError::Indexed),
+      (TessRange::Indices { .. }, Draw::Arrays { .. }) => return Err(TessRangeError::NotIndexed)
+    };
+
+    let repr = GLTess {
+      render: render_closure(self.vao, self.mode, draw),
+      vao: self.vao,
+      vbo: None,
+      ibo: self.ibo.clone(),
+      vertex_format: self.vertex_format.clone(),
+      vert_nb: self.vert_nb,
+      mode: self.mode,
+      draw: draw,
+      owned: false
+    };
+
+    Ok(TessSlice {
+      tess: tess::Tess { repr: repr },
+      _parent: PhantomData
+    })
+  }
+}
 
-      gl::BindVertexArray(vao);
+// Range of a tessellation drawn by another one, created with `GLTess::slice`.
+//
+// It derefs to a `Tess` to be used in render commands, and cannot outlive the tessellation it was
+// sliced from.
+pub struct TessSlice<'a> {
+  tess: Tess,
+  _parent: PhantomData<&'a GLTess>
+}
 
-      // vertex buffer
-      let vertex_buffer = Buffer::new(vert_nb);
-      vertex_buffer.fill(vertices);
+impl<'a> Deref for TessSlice<'a> {
+  type Target = Tess;
 
-      // once the vertex buffer is filled, we get its internal representation and we leak it so that
-      // it’s not dropped at the end of the scope
-      let vbo = vertex_buffer.repr.clone();
-      mem::forget(vertex_buffer);
+  fn deref(&self) -> &Self::Target {
+    &self.tess
+  }
+}
 
-      gl::BindBuffer(gl::ARRAY_BUFFER, vbo.handle);
-      set_vertex_pointers(&T::vertex_format());
+// Range of a tessellation to draw.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TessRange {
+  // vertices of a non-indexed tessellation
+  Vertices { start: usize, count: usize },
+  // indices of an indexed tessellation; base_vertex is added to every index
+  Indices { offset: usize, count: usize, base_vertex: GLint }
+}
 
-      // in case of indexed render, create the required objects
-      if let Some(indices) = indices {
-        let ind_nb = indices.len();
-        let index_buffer = Buffer::new(ind_nb);
-        index_buffer.fill(indices);
+// Reasons why a range of a tessellation cannot be drawn.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum TessRangeError {
+  // the range (start and count) doesn’t fit in the number of vertices or indices (last field)
+  OutOfRange(usize, usize, usize),
+  // the base vertex is negative or past the number of vertices
+  BaseVertexOutOfRange(GLint, usize),
+  // an index of the range, base vertex added, fetches a vertex past the number of vertices
+  VertexOutOfRange(usize, usize),
+  // a vertex range was asked on an indexed tessellation
+  Indexed,
+  // an index range was asked on a non-indexed tessellation
+  NotIndexed
+}
 
-        // same than vertex buffer, once the index buffer is filled, we leak it to the void
-        let ibo = index_buffer.repr.clone();
-        mem::forget(index_buffer);
+fn check_range(start: usize, count: usize, len: usize) -> Result<(), TessRangeError> {
+  match start.checked_add(count) {
+    Some(end) if end <= len => Ok(()),
+    _ => Err(TessRangeError::OutOfRange(start, count, len))
+  }
+}
 
-        gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, ibo.handle);
+// Largest index in a range of an index buffer, ignoring the primitive restart index.
+fn max_index(ibo: GLuint, index_type: GLenum, offset: usize, count: usize, primitive_restart: Option<u32>) -> Option<u32> {
+  let indices: Vec<u32> = match index_type {
+    gl::UNSIGNED_BYTE => read_indices::<u8>(ibo, offset, count).into_iter().map(|i| i as u32).collect(),
+    gl::UNSIGNED_SHORT => read_indices::<u16>(ibo, offset, count).into_iter().map(|i| i as u32).collect(),
+    _ => read_indices::<u32>(ibo, offset, count)
+  };
 
-        gl::BindVertexArray(0);
+  indices.into_iter().filter(|&i| Some(i) != primitive_restart).max()
+}
 
-        GLTess {
-          render: Box::new(move |size, instances| {
-            gl::BindVertexArray(vao);
+// Read a range of indices back from an index buffer.
+fn read_indices<I>(ibo: GLuint, offset: usize, count: usize) -> Vec<I> where I: Copy + Default {
+  let size = mem::size_of::<I>();
+  let mut indices = vec![I::default(); count];
 
-            set_point_line_size(mode, size);
+  // COPY_READ_BUFFER is used so that the element array binding of the bound vertex array is kept
+  unsafe {
+    gl::BindBuffer(gl::COPY_READ_BUFFER, ibo);
+    gl::GetBufferSubData(gl::COPY_READ_BUFFER, (offset * size) as GLintptr, (count * size) as GLsizeiptr, indices.as_mut_ptr() as *mut GLvoid);
+    gl::BindBuffer(gl::COPY_READ_BUFFER, 0);
+  }
 
-            if instances == 1 {
-              gl::DrawElements(opengl_mode(mode), ind_nb as GLsizei, gl::UNSIGNED_INT, ptr::null());
-            } else if instances > 1 {
-              gl::DrawElementsInstanced(opengl_mode(mode), ind_nb as GLsizei, gl::UNSIGNED_INT, ptr::null(), instances as GLsizei);
-            } else {
-              panic!("cannot index-render 0 instance");
-            }
-          }),
-          vao: vao,
-          vbo: Some(vbo),
-          ibo: Some(ibo),
-          vertex_format: T::vertex_format(),
-          vert_nb: vert_nb
-        }
-      } else {
-        gl::BindVertexArray(0);
+  indices
+}
 
-        GLTess {
-          render: Box::new(move |size, instances| {
-            gl::BindVertexArray(vao);
+// What the render closure of a tessellation draws.
+#[derive(Clone, Copy, Debug)]
+enum Draw {
+  Arrays { start: usize, count: usize },
+  Elements { index_type: GLenum, offset: usize, count: usize, base_vertex: GLint, primitive_restart: Option<u32> }
+}
 
-            set_point_line_size(mode, size);
+impl HasTess for GL33 {
+  type Tess = GLTess;
 
-            if instances == 1 {
-              gl::DrawArrays(opengl_mode(mode), 0, vert_nb as GLsizei);
-            } else if instances > 1 {
-              gl::DrawArraysInstanced(opengl_mode(mode), 0, vert_nb as GLsizei, instances as GLsizei);
-            } else {
-              panic!("cannot render 0 instance");
-            }
-          }),
-          vao: vao,
-          vbo: Some(vbo),
-          ibo: None,
-          vertex_format: T::vertex_format(),
-          vert_nb: vert_nb
-        }
-      }
-    }
+  fn new_tess<T>(mode: Mode, vertices: &[T], indices: Option<&[u32]>) -> Self::Tess where T: Vertex {
+    build_tess(mode, vertices, indices.map(TessIndices::U32), &TessOptions::default())
   }
 
   fn destroy_tess(tess: &mut Self::Tess) {
+    if !tess.owned {
+      return;
+    }
+
     // delete vertex array and all bound buffers
     unsafe {
       gl::DeleteVertexArrays(1, &tess.vao);
+      state::forget_vertex_array(tess.vao);
 
       if let &Some(ref vbo) = &tess.vbo {
         gl::DeleteBuffers(1, &vbo.handle);
+        state::forget_buffer(vbo.handle);
       }
 
       if let &Some(ref ibo) = &tess.ibo {
         gl::DeleteBuffers(1, &ibo.handle);
+        state::forget_buffer(ibo.handle);
       }
     }
   }
@@ -125,41 +208,297 @@ impl HasTess for GL33 {
 
     unsafe {
       gl::GenVertexArrays(1, &mut vao);
+    }
+
+    state::bind_vertex_array(vao);
+    state::bind_vertex_array(0);
+
+    let draw = Draw::Arrays { start: 0, count: vert_nb };
+
+    GLTess {
+      render: render_closure(vao, mode, draw),
+      vao: vao,
+      vbo: None,
+      ibo: None,
+      vertex_format: Vec::new(),
+      vert_nb: vert_nb,
+      mode: mode,
+      draw: draw,
+      owned: true
+    }
+  }
+
+  fn vertex_format(tesse: &Self::Tess) -> &VertexFormat {
+    &tesse.vertex_format
+  }
+
+  fn get_vertex_buffer_ref_mut(tess: &mut Self::Tess) -> Option<(&mut Self::ABuffer, usize)> {
+    let vert_nb = tess.vert_nb;
+    tess.vbo.as_mut().map(|vbo| (vbo, vert_nb))
+  }
+}
+
+// Options of tessellations created with `new_tess_with`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct TessOptions {
+  // index starting a new primitive; only for indexed line strips, triangle strips and triangle fans
+  pub primitive_restart: Option<u32>
+}
+
+// Indices of an indexed tessellation. Small index types save memory on small meshes.
+#[derive(Clone, Copy, Debug)]
+pub enum TessIndices<'a> {
+  U8(&'a [u8]),
+  U16(&'a [u16]),
+  U32(&'a [u32])
+}
+
+impl<'a> TessIndices<'a> {
+  fn len(&self) -> usize {
+    match *self {
+      TessIndices::U8(indices) => indices.len(),
+      TessIndices::U16(indices) => indices.len(),
+      TessIndices::U32(indices) => indices.len()
+    }
+  }
+
+  // Largest index the index type can represent.
+  fn max_index(&self) -> u32 {
+    match *self {
+      TessIndices::U8(_) => u8::max_value() as u32,
+      TessIndices::U16(_) => u16::max_value() as u32,
+      TessIndices::U32(_) => u32::max_value()
+    }
+  }
+
+  fn gl_type(&self) -> GLenum {
+    match *self {
+      TessIndices::U8(_) => gl::UNSIGNED_BYTE,
+      TessIndices::U16(_) => gl::UNSIGNED_SHORT,
+      TessIndices::U32(_) => gl::UNSIGNED_INT
+    }
+  }
+
+  // Find the first index referring to a vertex past vert_nb, ignoring the primitive restart index.
+  fn find_out_of_range(&self, vert_nb: usize, primitive_restart: Option<u32>) -> Option<u32> {
+    let out_of_range = |index: u32| Some(index) != primitive_restart && index as usize >= vert_nb;
+
+    match *self {
+      TessIndices::U8(indices) => indices.iter().map(|&i| i as u32).find(|&i| out_of_range(i)),
+      TessIndices::U16(indices) => indices.iter().map(|&i| i as u32).find(|&i| out_of_range(i)),
+      TessIndices::U32(indices) => indices.iter().cloned().find(|&i| out_of_range(i))
+    }
+  }
+}
+
+impl<'a> From<&'a [u8]> for TessIndices<'a> {
+  fn from(indices: &'a [u8]) -> Self {
+    TessIndices::U8(indices)
+  }
+}
+
+impl<'a> From<&'a [u16]> for TessIndices<'a> {
+  fn from(indices: &'a [u16]) -> Self {
+    TessIndices::U16(indices)
+  }
+}
+
+impl<'a> From<&'a [u32]> for TessIndices<'a> {
+  fn from(indices: &'a [u32]) -> Self {
+    TessIndices::U32(indices)
+  }
+}
+
+// Reasons why a tessellation cannot be created with `new_tess_with`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TessError {
+  // primitive restart is only available to indexed tessellations
+  PrimitiveRestartWithoutIndices,
+  // primitive restart is only available to line strips, triangle strips and triangle fans
+  PrimitiveRestartMode,
+  // the primitive restart index cannot be represented by the index type, so it never matches
+  PrimitiveRestartIndexType(u32)
+}
 
-      gl::BindVertexArray(vao);
-      gl::BindVertexArray(0);
+// Create a new tessellation with additional creation options.
+//
+// In debug builds, this panics if an index is out of range of the vertices.
+pub fn new_tess_with<T>(mode: Mode, vertices: &[T], indices: Option<TessIndices>, options: &TessOptions) -> Result<Tess, TessError> where T: Vertex {
+  check_tess_options(mode, indices, options)?;
+
+  Ok(tess::Tess {
+    repr: build_tess(mode, vertices, indices, options)
+  })
+}
+
+fn check_tess_options(mode: Mode, indices: Option<TessIndices>, options: &TessOptions) -> Result<(), TessError> {
+  if let Some(index) = options.primitive_restart {
+    let indices = indices.ok_or(TessError::PrimitiveRestartWithoutIndices)?;
+
+    if !is_strip_or_fan(mode) {
+      return Err(TessError::PrimitiveRestartMode);
+    }
+
+    if index > indices.max_index() {
+      return Err(TessError::PrimitiveRestartIndexType(index));
+    }
+  }
+
+  Ok(())
+}
+
+fn build_tess<T>(mode: Mode, vertices: &[T], indices: Option<TessIndices>, options: &TessOptions) -> GLTess where T: Vertex {
+  let mut vao: GLuint = 0;
+  let vert_nb = vertices.len();
+  let primitive_restart = options.primitive_restart;
+
+  if cfg!(debug_assertions) {
+    if let Some(index) = indices.and_then(|indices| indices.find_out_of_range(vert_nb, primitive_restart)) {
+      panic!("index {} is out of range of {} vertices", index, vert_nb);
+    }
+  }
+
+  unsafe {
+    gl::GenVertexArrays(1, &mut vao);
+
+    state::bind_vertex_array(vao);
+
+    // vertex buffer
+    let vertex_buffer = Buffer::new(vert_nb);
+    vertex_buffer.fill(vertices);
+
+    // once the vertex buffer is filled, we get its internal representation and we leak it so that
+    // it’s not dropped at the end of the scope
+    let vbo = vertex_buffer.repr.clone();
+    mem::forget(vertex_buffer);
+
+    gl::BindBuffer(gl::ARRAY_BUFFER, vbo.handle);
+    set_vertex_pointers(&T::vertex_format());
+
+    // in case of indexed render, create the required objects
+    if let Some(indices) = indices {
+      let ind_nb = indices.len();
+      let index_type = indices.gl_type();
+      let ibo = match indices {
+        TessIndices::U8(indices) => create_index_buffer(indices),
+        TessIndices::U16(indices) => create_index_buffer(indices),
+        TessIndices::U32(indices) => create_index_buffer(indices)
+      };
+
+      gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, ibo.handle);
+
+      state::bind_vertex_array(0);
+
+      let draw = Draw::Elements {
+        index_type: index_type,
+        offset: 0,
+        count: ind_nb,
+        base_vertex: 0,
+        primitive_restart: primitive_restart
+      };
+
+      GLTess {
+        render: render_closure(vao, mode, draw),
+        vao: vao,
+        vbo: Some(vbo),
+        ibo: Some(ibo),
+        vertex_format: T::vertex_format(),
+        vert_nb: vert_nb,
+        mode: mode,
+        draw: draw,
+        owned: true
+      }
+    } else {
+      state::bind_vertex_array(0);
+
+      let draw = Draw::Arrays { start: 0, count: vert_nb };
 
       GLTess {
-        render: Box::new(move |size, instances| {
-          gl::BindVertexArray(vao);
+        render: render_closure(vao, mode, draw),
+        vao: vao,
+        vbo: Some(vbo),
+        ibo: None,
+        vertex_format: T::vertex_format(),
+        vert_nb: vert_nb,
+        mode: mode,
+        draw: draw,
+        owned: true
+      }
+    }
+  }
+}
 
-          set_point_line_size(mode, size);
+// Build the closure rendering a tessellation, taking the point / line size and the number of
+// instances to render.
+fn render_closure(vao: GLuint, mode: Mode, draw: Draw) -> Box<Fn(Option<f32>, u32)> {
+  Box::new(move |size, instances| {
+    state::bind_vertex_array(vao);
+
+    set_point_line_size(mode, size);
+
+    match draw {
+      Draw::Arrays { start, count } => unsafe {
+        if instances == 1 {
+          gl::DrawArrays(opengl_mode(mode), start as GLint, count as GLsizei);
+        } else if instances > 1 {
+          gl::DrawArraysInstanced(opengl_mode(mode), start as GLint, count as GLsizei, instances as GLsizei);
+        } else {
+          panic!("cannot render 0 instance");
+        }
+      },
+
+      Draw::Elements { index_type, offset, count, base_vertex, primitive_restart } => {
+        if let Some(index) = primitive_restart {
+          state::set_capability(gl::PRIMITIVE_RESTART, true);
+          state::set_primitive_restart_index(index);
+        }
 
+        // offset in bytes of the first index in the index buffer
+        let first = (offset * index_size(index_type)) as *const GLvoid;
+
+        unsafe {
           if instances == 1 {
-            gl::DrawArrays(opengl_mode(mode), 0, vert_nb as GLsizei);
+            if base_vertex == 0 {
+              gl::DrawElements(opengl_mode(mode), count as GLsizei, index_type, first);
+            } else {
+              gl::DrawElementsBaseVertex(opengl_mode(mode), count as GLsizei, index_type, first, base_vertex);
+            }
           } else if instances > 1 {
-            gl::DrawArraysInstanced(opengl_mode(mode), 0, vert_nb as GLsizei, instances as GLsizei);
+            if base_vertex == 0 {
+              gl::DrawElementsInstanced(opengl_mode(mode), count as GLsizei, index_type, first, instances as GLsizei);
+            } else {
+              gl::DrawElementsInstancedBaseVertex(opengl_mode(mode), count as GLsizei, index_type, first, instances as GLsizei, base_vertex);
+            }
           } else {
-            panic!("cannot render 0 instance");
+            panic!("cannot index-render 0 instance");
           }
-        }),
-        vao: vao,
-        vbo: None,
-        ibo: None,
-        vertex_format: Vec::new(),
-        vert_nb: vert_nb
+        }
+
+        if primitive_restart.is_some() {
+          state::set_capability(gl::PRIMITIVE_RESTART, false);
+        }
       }
     }
-  }
+  })
+}
 
-  fn vertex_format(tesse: &Self::Tess) -> &VertexFormat {
-    &tesse.vertex_format
+fn index_size(index_type: GLenum) -> usize {
+  match index_type {
+    gl::UNSIGNED_BYTE => 1,
+    gl::UNSIGNED_SHORT => 2,
+    _ => 4
   }
+}
 
-  fn get_vertex_buffer_ref_mut(tess: &mut Self::Tess) -> Option<(&mut Self::ABuffer, usize)> {
-    let vert_nb = tess.vert_nb;
-    tess.vbo.as_mut().map(|vbo| (vbo, vert_nb))
-  }
+fn create_index_buffer<I>(indices: &[I]) -> GLBuffer {
+  let index_buffer = Buffer::new(indices.len());
+  index_buffer.fill(indices);
+
+  // same than vertex buffer, once the index buffer is filled, we leak it to the void
+  let ibo = index_buffer.repr.clone();
+  mem::forget(index_buffer);
+
+  ibo
 }
 
 // Give OpenGL types information on the content of the VBO by setting vertex formats and pointers
@@ -244,6 +583,13 @@ fn component_weight(f: &VertexComponentFormat) -> usize {
   dim_as_size(&f.dim) as usize * f.unit_size
 }
 
+fn is_strip_or_fan(mode: Mode) -> bool {
+  match mode {
+    Mode::LineStrip | Mode::TriangleStrip | Mode::TriangleFan => true,
+    _ => false
+  }
+}
+
 fn opengl_mode(mode: Mode) -> GLenum {
   match mode {
     Mode::Point => gl::POINTS,
@@ -271,3 +617,48 @@ fn off_align(off: usize, align: usize) -> usize {
   let a = align - 1;
   (off + a) & !a
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn range_within_bounds() {
+    assert_eq!(check_range(2, 3, 5), Ok(()));
+    assert_eq!(check_range(5, 0, 5), Ok(()));
+  }
+
+  #[test]
+  fn range_out_of_bounds() {
+    assert_eq!(check_range(3, 3, 5), Err(TessRangeError::OutOfRange(3, 3, 5)));
+  }
+
+  #[test]
+  fn primitive_restart_requires_indices() {
+    let options = TessOptions { primitive_restart: Some(0) };
+
+    assert_eq!(check_tess_options(Mode::TriangleStrip, None, &options), Err(TessError::PrimitiveRestartWithoutIndices));
+  }
+
+  #[test]
+  fn primitive_restart_requires_strip_or_fan() {
+    let indices: &[u16] = &[0, 1, 2];
+    let options = TessOptions { primitive_restart: Some(0xFFFF) };
+
+    assert_eq!(check_tess_options(Mode::Triangle, Some(indices.into()), &options), Err(TessError::PrimitiveRestartMode));
+    assert_eq!(check_tess_options(Mode::TriangleStrip, Some(indices.into()), &options), Ok(()));
+  }
+
+  #[test]
+  fn primitive_restart_index_fits_index_type() {
+    let indices: &[u8] = &[0, 1, 2];
+    let options = TessOptions { primitive_restart: Some(0xFFFF) };
+
+    assert_eq!(check_tess_options(Mode::TriangleStrip, Some(indices.into()), &options), Err(TessError::PrimitiveRestartIndexType(0xFFFF)));
+  }
+
+  #[test]
+  fn range_overflow() {
+    assert_eq!(check_range(usize::max_value(), 1, 5), Err(TessRangeError::OutOfRange(usize::max_value(), 1, 5)));
+  }
+}
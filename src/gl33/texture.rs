@@ -1,10 +1,12 @@
 use gl;
 use gl::types::*;
+use gl33::extension::has_extension;
+use gl33::state;
 use gl33::token::GL33;
 use luminance::texture::{self, DepthComparison, Dim, Dimensionable, Filter, HasTexture, Layerable,
                          Layering, Result, Sampler, TextureError, Wrap, dim_capacity};
 use luminance::pixel::{Pixel, PixelFormat};
-use pixel::{gl_pixel_format, pixel_components};
+use pixel::{gl_pixel_format, pixel_alignment, pixel_components, zeroed_texels};
 use std::mem;
 use std::os::raw::c_void;
 use std::ptr;
@@ -14,16 +16,108 @@ pub type Texture<L, D, P> = texture::Texture<GL33, L, D, P>;
 // OpenGL texture representation.
 pub struct GLTexture {
   pub handle: GLuint, // handle to GPU texture object
-  pub target: GLenum // « type » of the texture; used for bindings
+  pub target: GLenum, // « type » of the texture; used for bindings
+  pub owned: bool // whether the GPU texture object is freed along with this texture
 }
 
 impl GLTexture {
   pub fn new(handle: GLuint, target: GLenum) -> Self {
     GLTexture {
       handle: handle,
-      target: target
+      target: target,
+      owned: true
     }
   }
+
+  // Texture referring to a GPU texture object owned by another texture.
+  pub fn borrowed(handle: GLuint, target: GLenum) -> Self {
+    GLTexture {
+      handle: handle,
+      target: target,
+      owned: false
+    }
+  }
+
+  // Change the swizzle mask of the texture; i.e. what shaders see when sampling it.
+  pub fn set_swizzle(&self, swizzle: Swizzle) {
+    state::bind_texture(self.target, self.handle);
+    apply_swizzle_to_texture(self.target, swizzle);
+    state::bind_texture(self.target, 0);
+  }
+}
+
+// Source of a channel when a texture is sampled.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SwizzleComponent {
+  Red,
+  Green,
+  Blue,
+  Alpha,
+  Zero,
+  One
+}
+
+// Swizzle mask of a texture. Each field gives the source of the corresponding sampled channel.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Swizzle {
+  pub r: SwizzleComponent,
+  pub g: SwizzleComponent,
+  pub b: SwizzleComponent,
+  pub a: SwizzleComponent
+}
+
+impl Swizzle {
+  pub fn new(r: SwizzleComponent, g: SwizzleComponent, b: SwizzleComponent, a: SwizzleComponent) -> Self {
+    Swizzle {
+      r: r,
+      g: g,
+      b: b,
+      a: a
+    }
+  }
+
+  // Present a single-channel texture as (1, 1, 1, r); useful for masks.
+  pub fn alpha_mask() -> Self {
+    Swizzle::new(SwizzleComponent::One, SwizzleComponent::One, SwizzleComponent::One, SwizzleComponent::Red)
+  }
+
+  // Present BGRA-ordered texels as RGBA.
+  pub fn bgra() -> Self {
+    Swizzle::new(SwizzleComponent::Blue, SwizzleComponent::Green, SwizzleComponent::Red, SwizzleComponent::Alpha)
+  }
+}
+
+impl Default for Swizzle {
+  fn default() -> Self {
+    Swizzle::new(SwizzleComponent::Red, SwizzleComponent::Green, SwizzleComponent::Blue, SwizzleComponent::Alpha)
+  }
+}
+
+// Create a new texture with a swizzle mask.
+pub fn new_texture_with_swizzle<L, D, P>(size: D::Size, mipmaps: usize, sampler: &Sampler, swizzle: Swizzle) -> Result<Texture<L, D, P>>
+    where L: Layerable,
+          D: Dimensionable,
+          D::Size: Copy,
+          P: Pixel {
+  let texture = Texture::new(size, mipmaps, sampler)?;
+  texture.repr.set_swizzle(swizzle);
+
+  Ok(texture)
+}
+
+// Create a new layered texture with the given number of layers; cubemap arrays have that many
+// cubemaps. Layered textures created through luminance only have one layer.
+pub fn new_layered_texture<D, P>(size: D::Size, layers: u32, mipmaps: usize, sampler: &Sampler) -> Result<Texture<texture::Layered, D, P>>
+    where D: Dimensionable,
+          D::Size: Copy,
+          P: Pixel {
+  let texture = Texture::new(size, mipmaps, sampler)?;
+
+  state::bind_texture(texture.repr.target, texture.repr.handle);
+  let created = create_texture_storage::<texture::Layered, D>(size, layers, texture.mipmaps, P::pixel_format());
+  state::bind_texture(texture.repr.target, 0);
+
+  created.map(|_| texture)
 }
 
 impl HasTexture for GL33 {
@@ -39,21 +133,22 @@ impl HasTexture for GL33 {
 
     unsafe {
       gl::GenTextures(1, &mut texture);
-      gl::BindTexture(target, texture);
+      state::bind_texture(target, texture);
     }
     
-    create_texture::<L, D>(target, size, mipmaps, P::pixel_format(), sampler)?;
+    create_texture::<L, D>(target, size, 1, mipmaps, P::pixel_format(), sampler)?;
 
     // FIXME: maybe we can get rid of this
-    unsafe {
-      gl::BindTexture(target, 0);
-    }
+    state::bind_texture(target, 0);
 
     Ok(GLTexture::new(texture, target))
   }
 
   fn free(texture: &mut Self::ATexture) {
-    unsafe { gl::DeleteTextures(1, &texture.handle) }
+    if texture.owned {
+      unsafe { gl::DeleteTextures(1, &texture.handle) };
+      state::forget_texture(texture.handle);
+    }
   }
 
   fn clear_part<L, D, P>(texture: &Self::ATexture, gen_mipmaps: bool, off: D::Offset, size: D::Size, pixel: P::Encoding)
@@ -64,7 +159,7 @@ impl HasTexture for GL33 {
   fn upload_part<L, D, P>(texture: &Self::ATexture, gen_mipmaps: bool, off: D::Offset, size: D::Size, texels: &[P::Encoding])
       where L: Layerable, D::Offset: Copy, D::Size: Copy, D: Dimensionable, P: Pixel {
     unsafe {
-      gl::BindTexture(texture.target, texture.handle);
+      state::bind_texture(texture.target, texture.handle);
 
       upload_texels::<L, D, P, P::Encoding>(texture.target, off, size, texels);
 
@@ -72,14 +167,14 @@ impl HasTexture for GL33 {
         gl::GenerateMipmap(texture.target);
       }
 
-      gl::BindTexture(texture.target, 0);
+      state::bind_texture(texture.target, 0);
     }
   }
 
   fn upload_part_raw<L, D, P>(texture: &Self::ATexture, gen_mipmaps: bool, off: D::Offset, size: D::Size, texels: &[P::RawEncoding])
       where L: Layerable, D::Offset: Copy, D::Size: Copy, D: Dimensionable, P: Pixel {
     unsafe {
-      gl::BindTexture(texture.target, texture.handle);
+      state::bind_texture(texture.target, texture.handle);
 
       upload_texels::<L, D, P, P::RawEncoding>(texture.target, off, size, texels);
 
@@ -87,39 +182,158 @@ impl HasTexture for GL33 {
         gl::GenerateMipmap(texture.target);
       }
 
-      gl::BindTexture(texture.target, 0);
+      state::bind_texture(texture.target, 0);
     }
   }
 
-  // FIXME: cubemaps?
   fn get_raw_texels<P>(texture: &Self::ATexture) -> Vec<P::RawEncoding> where P: Pixel, P::RawEncoding: Copy {
-    let mut texels = Vec::new();
-    let pf = P::pixel_format();
+    let size = level_size(texture, 0);
+    read_level::<P>(texture, 0, size)
+  }
+}
+
+// Reasons why texels cannot be read back from a texture.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ReadTexelsError {
+  // the region, expressed in the level’s memory layout (x, y, z, w, h, d), doesn’t fit in the
+  // mipmap level
+  RegionOutOfBounds(u32, u32, u32, u32, u32, u32)
+}
+
+// Read back a rectangular region of a mipmap level of a texture.
+//
+// The offset and size are expressed in the texture’s dimension. Layered textures are read on all
+// their layers, and cubemaps on the face pointed to by the offset. The region must fit in the level.
+//
+// Only the region is transferred when ARB_get_texture_sub_image is available. Otherwise, the whole
+// level is read back and the region is extracted from it, which costs as much as reading the level.
+pub fn get_raw_texels_part<L, D, P>(texture: &GLTexture, level: usize, off: D::Offset, size: D::Size) -> ::std::result::Result<Vec<P::RawEncoding>, ReadTexelsError>
+    where L: Layerable,
+          D: Dimensionable,
+          D::Offset: Copy,
+          D::Size: Copy,
+          P: Pixel,
+          P::RawEncoding: Copy {
+  let pf = P::pixel_format();
+  let components = pixel_components(pf);
+  let (lw, lh, ld) = level_size(texture, level);
+
+  // region to read, expressed in the level’s memory layout (x, y, z, w, h, d); offsets the dimension
+  // doesn’t have are ignored
+  let (x, y, z, w, h, d) = match (L::layering(), D::dim()) {
+    (Layering::Flat, Dim::Dim1) => (D::x_offset(off), 0, 0, D::width(size), 1, 1),
+    (Layering::Flat, Dim::Dim2) => (D::x_offset(off), D::y_offset(off), 0, D::width(size), D::height(size), 1),
+    (Layering::Flat, Dim::Dim3) => (D::x_offset(off), D::y_offset(off), D::z_offset(off), D::width(size), D::height(size), D::depth(size)),
+    (Layering::Flat, Dim::Cubemap) => (D::x_offset(off), D::y_offset(off), D::z_offset(off), D::width(size), D::height(size), 1),
+    (Layering::Layered, Dim::Dim1) => (D::x_offset(off), 0, 0, D::width(size), lh, 1),
+    (Layering::Layered, _) => (D::x_offset(off), D::y_offset(off), 0, D::width(size), D::height(size), ld)
+  };
+
+  let fits = |off: u32, len: u32, level_len: u32| off.checked_add(len).map_or(false, |end| end <= level_len);
+
+  if !fits(x, w, lw) || !fits(y, h, lh) || !fits(z, d, ld) {
+    return Err(ReadTexelsError::RegionOutOfBounds(x, y, z, w, h, d));
+  }
+
+  let row_len = w as usize * components;
+  let len = row_len * h as usize * d as usize;
+
+  if len == 0 {
+    return Ok(Vec::new());
+  }
+
+  if has_extension("GL_ARB_get_texture_sub_image") && gl::GetTextureSubImage::is_loaded() {
     let (format, _, ty) = gl_pixel_format(pf).unwrap();
+    let mut texels: Vec<P::RawEncoding> = zeroed_texels(len);
+    let bytes = len * mem::size_of::<P::RawEncoding>();
+
+    set_pack_alignment(pf);
 
     unsafe {
-      let mut w = 0;
-      let mut h = 0;
+      gl::GetTextureSubImage(texture.handle, level as GLint, x as GLint, y as GLint, z as GLint, w as GLsizei, h as GLsizei, d as GLsizei, format, ty, bytes as GLsizei, texels.as_mut_ptr() as *mut c_void);
+    }
+
+    return Ok(texels);
+  }
+
+  let level_texels = read_level::<P>(texture, level, (lw, lh, ld));
+  let mut texels = Vec::with_capacity(len);
 
-      gl::BindTexture(texture.target, texture.handle);
+  for k in z..z + d {
+    for j in y..y + h {
+      let start = ((k * lh + j) * lw + x) as usize * components;
+      texels.extend_from_slice(&level_texels[start..start + row_len]);
+    }
+  }
+
+  Ok(texels)
+}
 
-      // retrieve the size of the texture (w and h)
-      gl::GetTexLevelParameteriv(texture.target, 0, gl::TEXTURE_WIDTH, &mut w);
-      gl::GetTexLevelParameteriv(texture.target, 0, gl::TEXTURE_HEIGHT, &mut h);
+// Size of a mipmap level of a texture (w, h, d).
+//
+// Cubemap faces are counted as if they were the depth of the level. The same applies to the layers
+// of layered textures.
+fn level_size(texture: &GLTexture, level: usize) -> (u32, u32, u32) {
+  let targets = image_targets(texture.target);
+  let mut w = 0;
+  let mut h = 0;
+  let mut d = 0;
 
-      // resize the vec to allocate enough space to host the returned texels
-      texels.resize((w * h) as usize * pixel_components(pf), mem::uninitialized());
+  state::bind_texture(texture.target, texture.handle);
+
+  // the height and depth are 1 when the target doesn’t use them
+  unsafe {
+    gl::GetTexLevelParameteriv(targets[0], level as GLint, gl::TEXTURE_WIDTH, &mut w);
+    gl::GetTexLevelParameteriv(targets[0], level as GLint, gl::TEXTURE_HEIGHT, &mut h);
+    gl::GetTexLevelParameteriv(targets[0], level as GLint, gl::TEXTURE_DEPTH, &mut d);
+  }
+
+  state::bind_texture(texture.target, 0);
+
+  (w as u32, h as u32, (d as usize * targets.len()) as u32)
+}
+
+// Read back a whole mipmap level of a texture, which size is given by `level_size`.
+//
+// Cubemap faces are laid out one after the other, as if they were the depth of the level. The same
+// applies to the layers of layered textures.
+fn read_level<P>(texture: &GLTexture, level: usize, (w, h, d): (u32, u32, u32)) -> Vec<P::RawEncoding> where P: Pixel, P::RawEncoding: Copy {
+  let pf = P::pixel_format();
+  let (format, _, ty) = gl_pixel_format(pf).unwrap();
+  let targets = image_targets(texture.target);
+  let len = w as usize * h as usize * d as usize * pixel_components(pf);
+  let image_len = len / targets.len();
 
-      gl::GetTexImage(texture.target, 0, format, ty, texels.as_mut_ptr() as *mut c_void);
+  // zeroed, so that texels OpenGL fails to write are still initialized
+  let mut texels: Vec<P::RawEncoding> = zeroed_texels(len);
 
-      gl::BindTexture(texture.target, 0);
+  state::bind_texture(texture.target, texture.handle);
+
+  set_pack_alignment(pf);
+
+  for (i, image_target) in targets.iter().enumerate() {
+    unsafe {
+      let ptr = texels.as_mut_ptr().offset((i * image_len) as isize);
+      gl::GetTexImage(*image_target, level as GLint, format, ty, ptr as *mut c_void);
     }
+  }
+
+  state::bind_texture(texture.target, 0);
 
-    texels
+  texels
+}
+
+// Targets to use to address the images of a texture (i.e. one per face for cubemaps).
+fn image_targets(target: GLenum) -> Vec<GLenum> {
+  if target == gl::TEXTURE_CUBE_MAP {
+    (gl::TEXTURE_CUBE_MAP_POSITIVE_X..gl::TEXTURE_CUBE_MAP_POSITIVE_X + 6).collect()
+  } else {
+    vec![target]
   }
 }
 
-pub fn create_texture<L, D>(target: GLenum, size: D::Size, mipmaps: usize, pf: PixelFormat, sampler: &Sampler) -> Result<()>
+// Create the storage of the currently bound texture. layers is ignored for flat textures.
+pub fn create_texture<L, D>(target: GLenum, size: D::Size, layers: u32, mipmaps: usize, pf: PixelFormat, sampler: &Sampler) -> Result<()>
     where L: Layerable,
           D: Dimensionable,
           D::Size: Copy {
@@ -127,7 +341,19 @@ pub fn create_texture<L, D>(target: GLenum, size: D::Size, mipmaps: usize, pf: P
 
   apply_sampler_to_texture(target, sampler);
 
-  create_texture_storage::<L, D>(size, mipmaps, pf)
+  create_texture_storage::<L, D>(size, layers, mipmaps, pf)
+}
+
+// Create the storage of a multisample 2D texture. Multisample textures have neither mipmaps nor
+// sampling state.
+pub fn create_multisample_texture(w: u32, h: u32, samples: u32, pf: PixelFormat) -> Result<()> {
+  match gl_pixel_format(pf) {
+    Some((_, iformat, _)) => {
+      unsafe { gl::TexImage2DMultisample(gl::TEXTURE_2D_MULTISAMPLE, samples as GLsizei, iformat, w as GLsizei, h as GLsizei, gl::TRUE) };
+      Ok(())
+    },
+    None => Err(TextureError::TextureStorageCreationFailed(format!("unsupported texture pixel format: {:?}", pf)))
+  }
 }
 
 pub fn to_target(l: Layering, d: Dim) -> GLenum {
@@ -147,7 +373,7 @@ pub fn to_target(l: Layering, d: Dim) -> GLenum {
   }
 }
 
-fn create_texture_storage<L, D>(size: D::Size, mipmaps: usize, pf: PixelFormat) -> Result<()>
+fn create_texture_storage<L, D>(size: D::Size, layers: u32, mipmaps: usize, pf: PixelFormat) -> Result<()>
     where L: Layerable,
           D: Dimensionable,
           D::Size: Copy {
@@ -176,7 +402,22 @@ fn create_texture_storage<L, D>(size: D::Size, mipmaps: usize, pf: PixelFormat)
           create_cubemap_storage(format, iformat, encoding, D::width(size), mipmaps);
           Ok(())
         },
-        _ => Err(TextureError::TextureStorageCreationFailed(format!("unsupported texture OpenGL pixel format: {:?}", glf)))
+        // 1D texture array; each layer is a row
+        (Layering::Layered, Dim::Dim1) => {
+          create_texture_1d_array_storage(format, iformat, encoding, D::width(size), layers, mipmaps);
+          Ok(())
+        },
+        // 2D texture array
+        (Layering::Layered, Dim::Dim2) => {
+          create_texture_array_storage(gl::TEXTURE_2D_ARRAY, format, iformat, encoding, D::width(size), D::height(size), layers, mipmaps);
+          Ok(())
+        },
+        // cubemap array; each cubemap takes six layers
+        (Layering::Layered, Dim::Cubemap) => {
+          create_texture_array_storage(gl::TEXTURE_CUBE_MAP_ARRAY, format, iformat, encoding, D::width(size), D::width(size), layers * 6, mipmaps);
+          Ok(())
+        },
+        _ => Err(TextureError::TextureStorageCreationFailed(format!("unsupported texture target: {:?} {:?}", L::layering(), D::dim())))
       }
     },
     None => Err(TextureError::TextureStorageCreationFailed(format!("unsupported texture pixel format: {:?}", pf)))
@@ -216,7 +457,29 @@ fn create_cubemap_storage(format: GLenum, iformat: GLenum, encoding: GLenum, s:
   for level in 0..mipmaps {
     let s = s / 2u32.pow(level as u32);
 
-    unsafe { gl::TexImage2D(gl::TEXTURE_CUBE_MAP, level as GLint, iformat as GLint, s as GLsizei, s as GLsizei, 0, format, encoding, ptr::null()) };
+    // each face has its own storage
+    for face in image_targets(gl::TEXTURE_CUBE_MAP) {
+      unsafe { gl::TexImage2D(face, level as GLint, iformat as GLint, s as GLsizei, s as GLsizei, 0, format, encoding, ptr::null()) };
+    }
+  }
+}
+
+fn create_texture_1d_array_storage(format: GLenum, iformat: GLenum, encoding: GLenum, w: u32, layers: u32, mipmaps: usize) {
+  for level in 0..mipmaps {
+    let w = w / 2u32.pow(level as u32);
+
+    unsafe { gl::TexImage2D(gl::TEXTURE_1D_ARRAY, level as GLint, iformat as GLint, w as GLsizei, layers as GLsizei, 0, format, encoding, ptr::null()) };
+  }
+}
+
+// Storage of 2D and cubemap arrays; the number of layers doesn’t shrink with the mipmap levels.
+fn create_texture_array_storage(target: GLenum, format: GLenum, iformat: GLenum, encoding: GLenum, w: u32, h: u32, layers: u32, mipmaps: usize) {
+  for level in 0..mipmaps {
+    let div = 2u32.pow(level as u32);
+    let w = w / div;
+    let h = h / div;
+
+    unsafe { gl::TexImage3D(target, level as GLint, iformat as GLint, w as GLsizei, h as GLsizei, layers as GLsizei, 0, format, encoding, ptr::null()) };
   }
 }
 
@@ -246,6 +509,28 @@ fn apply_sampler_to_texture(target: GLenum, sampler: &Sampler) {
   }
 }
 
+fn apply_swizzle_to_texture(target: GLenum, swizzle: Swizzle) {
+  let mask = [
+    from_swizzle_component(swizzle.r) as GLint,
+    from_swizzle_component(swizzle.g) as GLint,
+    from_swizzle_component(swizzle.b) as GLint,
+    from_swizzle_component(swizzle.a) as GLint
+  ];
+
+  unsafe { gl::TexParameteriv(target, gl::TEXTURE_SWIZZLE_RGBA, mask.as_ptr()) };
+}
+
+fn from_swizzle_component(component: SwizzleComponent) -> GLenum {
+  match component {
+    SwizzleComponent::Red => gl::RED,
+    SwizzleComponent::Green => gl::GREEN,
+    SwizzleComponent::Blue => gl::BLUE,
+    SwizzleComponent::Alpha => gl::ALPHA,
+    SwizzleComponent::Zero => gl::ZERO,
+    SwizzleComponent::One => gl::ONE
+  }
+}
+
 fn from_wrap(wrap: Wrap) -> GLenum {
   match wrap {
     Wrap::ClampToEdge => gl::CLAMP_TO_EDGE,
@@ -254,14 +539,14 @@ fn from_wrap(wrap: Wrap) -> GLenum {
   }
 }
 
-fn from_filter(filter: Filter) -> GLenum {
+pub fn from_filter(filter: Filter) -> GLenum {
   match filter {
     Filter::Nearest => gl::NEAREST,
     Filter::Linear => gl::LINEAR
   }
 }
 
-fn from_depth_comparison(fun: DepthComparison) -> GLenum {
+pub fn from_depth_comparison(fun: DepthComparison) -> GLenum {
   match fun {
     DepthComparison::Never => gl::NEVER,
     DepthComparison::Always => gl::ALWAYS,
@@ -285,6 +570,8 @@ fn upload_texels<L, D, P, T>(target: GLenum, off: D::Offset, size: D::Size, texe
 
   match gl_pixel_format(pf) {
     Some((format, _, encoding)) => {
+      set_unpack_alignment(pf);
+
       match L::layering() {
         Layering::Flat => {
           match D::dim() {
@@ -300,3 +587,84 @@ fn upload_texels<L, D, P, T>(target: GLenum, off: D::Offset, size: D::Size, texe
     None => panic!("unknown pixel format")
   }
 }
+
+// Set the unpack pixel store so that tightly packed texels are read correctly. A row length and an
+// image height of 0 – the defaults – stand for the size of the uploaded region.
+fn set_unpack_alignment(pf: PixelFormat) {
+  unsafe {
+    gl::PixelStorei(gl::UNPACK_ALIGNMENT, pixel_alignment(pf));
+    gl::PixelStorei(gl::UNPACK_ROW_LENGTH, 0);
+    gl::PixelStorei(gl::UNPACK_IMAGE_HEIGHT, 0);
+    gl::PixelStorei(gl::UNPACK_SKIP_PIXELS, 0);
+    gl::PixelStorei(gl::UNPACK_SKIP_ROWS, 0);
+    gl::PixelStorei(gl::UNPACK_SKIP_IMAGES, 0);
+  }
+}
+
+// Set the pack pixel store so that texels are written back tightly packed.
+pub fn set_pack_alignment(pf: PixelFormat) {
+  unsafe {
+    gl::PixelStorei(gl::PACK_ALIGNMENT, pixel_alignment(pf));
+    gl::PixelStorei(gl::PACK_ROW_LENGTH, 0);
+    gl::PixelStorei(gl::PACK_IMAGE_HEIGHT, 0);
+    gl::PixelStorei(gl::PACK_SKIP_PIXELS, 0);
+    gl::PixelStorei(gl::PACK_SKIP_ROWS, 0);
+    gl::PixelStorei(gl::PACK_SKIP_IMAGES, 0);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use gl;
+  use gl::types::*;
+  use luminance::pixel::RGBA32F;
+  use luminance::texture::{Cubemap, Dim1, Dim2, Flat, Layered};
+  use std::cell::RefCell;
+  use std::os::raw::c_void;
+
+  use super::*;
+
+  // (target, level, w, h, d)
+  type Image = (GLenum, GLint, GLsizei, GLsizei, GLsizei);
+
+  thread_local!(static IMAGES: RefCell<Vec<Image>> = RefCell::new(Vec::new()));
+
+  extern "system" fn tex_image_2d_stub(target: GLenum, level: GLint, _: GLint, w: GLsizei, h: GLsizei, _: GLint, _: GLenum, _: GLenum, _: *const c_void) {
+    IMAGES.with(|images| images.borrow_mut().push((target, level, w, h, 1)));
+  }
+
+  extern "system" fn tex_image_3d_stub(target: GLenum, level: GLint, _: GLint, w: GLsizei, h: GLsizei, d: GLsizei, _: GLint, _: GLenum, _: GLenum, _: *const c_void) {
+    IMAGES.with(|images| images.borrow_mut().push((target, level, w, h, d)));
+  }
+
+  // Record the images specified by the storage functions instead of creating them.
+  fn record_images<F>(f: F) -> Vec<Image> where F: FnOnce() {
+    gl::TexImage2D::load_with(|_| tex_image_2d_stub as *const c_void);
+    gl::TexImage3D::load_with(|_| tex_image_3d_stub as *const c_void);
+    IMAGES.with(|images| images.borrow_mut().clear());
+
+    f();
+
+    IMAGES.with(|images| images.borrow().clone())
+  }
+
+  #[test]
+  fn cubemap_storage_covers_every_face() {
+    let images = record_images(|| create_texture_storage::<Flat, Cubemap>(8, 1, 2, RGBA32F::pixel_format()).unwrap());
+    let faces = |level, s| (0..6).map(move |i| (gl::TEXTURE_CUBE_MAP_POSITIVE_X + i, level, s, s, 1));
+
+    assert_eq!(images, faces(0, 8).chain(faces(1, 4)).collect::<Vec<_>>());
+  }
+
+  #[test]
+  fn layered_storage_has_every_layer() {
+    let images = record_images(|| create_texture_storage::<Layered, Dim2>((8, 4), 3, 2, RGBA32F::pixel_format()).unwrap());
+    assert_eq!(images, vec![(gl::TEXTURE_2D_ARRAY, 0, 8, 4, 3), (gl::TEXTURE_2D_ARRAY, 1, 4, 2, 3)]);
+
+    let images = record_images(|| create_texture_storage::<Layered, Cubemap>(8, 2, 1, RGBA32F::pixel_format()).unwrap());
+    assert_eq!(images, vec![(gl::TEXTURE_CUBE_MAP_ARRAY, 0, 8, 8, 12)]);
+
+    let images = record_images(|| create_texture_storage::<Layered, Dim1>(8, 5, 1, RGBA32F::pixel_format()).unwrap());
+    assert_eq!(images, vec![(gl::TEXTURE_1D_ARRAY, 0, 8, 5, 1)]);
+  }
+}
@@ -1,14 +1,21 @@
 use gl;
 use gl::types::*;
 use luminance::pixel::{Format, PixelFormat, Type};
+use std::ptr;
 
 // Return the format, internal sized-format and type.
 pub fn gl_pixel_format(pf: PixelFormat) -> Option<(GLenum, GLenum, GLenum)> {
   match (pf.format, pf.encoding) {
+    (Format::R(8), Type::Unsigned) => Some((gl::RED_INTEGER, gl::R8UI, gl::UNSIGNED_BYTE)),
+    (Format::RG(8, 8), Type::Unsigned) => Some((gl::RG_INTEGER, gl::RG8UI, gl::UNSIGNED_BYTE)),
     (Format::RGB(8, 8, 8), Type::Unsigned) => Some((gl::RGB_INTEGER, gl::RGB8UI, gl::UNSIGNED_BYTE)),
     (Format::RGBA(8, 8, 8, 8), Type::Unsigned) => Some((gl::RGBA_INTEGER, gl::RGBA8UI, gl::UNSIGNED_BYTE)),
+    (Format::R(32), Type::Floating) => Some((gl::RED, gl::R32F, gl::FLOAT)),
+    (Format::RG(32, 32), Type::Floating) => Some((gl::RG, gl::RG32F, gl::FLOAT)),
     (Format::RGB(32, 32, 32), Type::Floating) => Some((gl::RGB, gl::RGB32F, gl::FLOAT)),
     (Format::RGBA(32, 32, 32, 32), Type::Floating) => Some((gl::RGBA, gl::RGBA32F, gl::FLOAT)),
+    (Format::Depth(16), Type::Unsigned) => Some((gl::DEPTH_COMPONENT, gl::DEPTH_COMPONENT16, gl::UNSIGNED_SHORT)),
+    (Format::Depth(32), Type::Unsigned) => Some((gl::DEPTH_COMPONENT, gl::DEPTH_COMPONENT32, gl::UNSIGNED_INT)),
     (Format::Depth(32), Type::Floating) => Some((gl::DEPTH_COMPONENT, gl::DEPTH_COMPONENT32F, gl::FLOAT)),
     _ => panic!("unsupported pixel format")
   }
@@ -17,9 +24,71 @@ pub fn gl_pixel_format(pf: PixelFormat) -> Option<(GLenum, GLenum, GLenum)> {
 // Return the number of components.
 pub fn pixel_components(pf: PixelFormat) -> usize {
   match pf.format {
+    Format::R(_) => 1,
+    Format::RG(_, _) => 2,
     Format::RGB(_, _, _) => 3,
     Format::RGBA(_, _, _, _) => 4,
-    Format::Depth(_) => 1,
-    _ => panic!("unsupported pixel format")
+    Format::Depth(_) => 1
+  }
+}
+
+// Return the size in bytes of a single pixel.
+pub fn pixel_bytes(pf: PixelFormat) -> usize {
+  let bits = match pf.format {
+    Format::R(r) => r as usize,
+    Format::RG(r, g) => r as usize + g as usize,
+    Format::RGB(r, g, b) => r as usize + g as usize + b as usize,
+    Format::RGBA(r, g, b, a) => r as usize + g as usize + b as usize + a as usize,
+    Format::Depth(d) => d as usize
+  };
+
+  bits / 8
+}
+
+// Return the pixel store alignment to use for tightly packed pixels of this format.
+//
+// OpenGL rows are 4-byte aligned by default, which doesn’t hold for, e.g., RGB8 rows of odd width.
+pub fn pixel_alignment(pf: PixelFormat) -> GLint {
+  match pixel_bytes(pf) {
+    b if b % 8 == 0 => 8,
+    b if b % 4 == 0 => 4,
+    b if b % 2 == 0 => 2,
+    _ => 1
+  }
+}
+
+// Allocate zeroed raw texels, for OpenGL to read texels back into.
+//
+// Raw encodings are integers or floats, for which zeroed memory is a valid value; if OpenGL fails to
+// write the texels, they’re left zeroed.
+pub fn zeroed_texels<T>(len: usize) -> Vec<T> where T: Copy {
+  let mut texels = Vec::with_capacity(len);
+
+  unsafe {
+    ptr::write_bytes(texels.as_mut_ptr(), 0, len);
+    texels.set_len(len);
+  }
+
+  texels
+}
+
+// Return the format and type to use to transfer texels of an internal sized-format.
+pub fn gl_transfer_format(iformat: GLenum) -> Option<(GLenum, GLenum)> {
+  match iformat {
+    gl::R8UI => Some((gl::RED_INTEGER, gl::UNSIGNED_BYTE)),
+    gl::RG8UI => Some((gl::RG_INTEGER, gl::UNSIGNED_BYTE)),
+    gl::RGB8UI => Some((gl::RGB_INTEGER, gl::UNSIGNED_BYTE)),
+    gl::RGBA8UI => Some((gl::RGBA_INTEGER, gl::UNSIGNED_BYTE)),
+    gl::R32F => Some((gl::RED, gl::FLOAT)),
+    gl::RG32F => Some((gl::RG, gl::FLOAT)),
+    gl::RGB32F => Some((gl::RGB, gl::FLOAT)),
+    gl::RGBA32F => Some((gl::RGBA, gl::FLOAT)),
+    gl::DEPTH_COMPONENT16 => Some((gl::DEPTH_COMPONENT, gl::UNSIGNED_SHORT)),
+    gl::DEPTH_COMPONENT24 | gl::DEPTH_COMPONENT32 => Some((gl::DEPTH_COMPONENT, gl::UNSIGNED_INT)),
+    gl::DEPTH_COMPONENT32F => Some((gl::DEPTH_COMPONENT, gl::FLOAT)),
+    gl::DEPTH24_STENCIL8 => Some((gl::DEPTH_STENCIL, gl::UNSIGNED_INT_24_8)),
+    gl::DEPTH32F_STENCIL8 => Some((gl::DEPTH_STENCIL, gl::FLOAT_32_UNSIGNED_INT_24_8_REV)),
+    gl::STENCIL_INDEX8 => Some((gl::STENCIL_INDEX, gl::UNSIGNED_BYTE)),
+    _ => None
   }
 }